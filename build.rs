@@ -0,0 +1,96 @@
+use tonic_build::manual::{Builder, Method, Service};
+
+// the OLM operator-registry api.Registry service (pkg/api/registry.proto)
+// the messages are defined in src/registry/model.rs so no protoc is required
+const METHODS: [(&str, &str, &str, &str, bool); 10] = [
+    (
+        "list_packages",
+        "ListPackages",
+        "ListPackageRequest",
+        "PackageName",
+        true,
+    ),
+    (
+        "get_package",
+        "GetPackage",
+        "GetPackageRequest",
+        "Package",
+        false,
+    ),
+    (
+        "get_bundle",
+        "GetBundle",
+        "GetBundleRequest",
+        "Bundle",
+        false,
+    ),
+    (
+        "get_bundle_for_channel",
+        "GetBundleForChannel",
+        "GetBundleInChannelRequest",
+        "Bundle",
+        false,
+    ),
+    (
+        "get_channel_entries_that_replace",
+        "GetChannelEntriesThatReplace",
+        "GetAllReplacementsRequest",
+        "ChannelEntry",
+        true,
+    ),
+    (
+        "get_bundle_that_replaces",
+        "GetBundleThatReplaces",
+        "GetReplacementRequest",
+        "Bundle",
+        false,
+    ),
+    (
+        "get_channel_entries_that_provide",
+        "GetChannelEntriesThatProvide",
+        "GetAllProvidersRequest",
+        "ChannelEntry",
+        true,
+    ),
+    (
+        "get_latest_channel_entries_that_provide",
+        "GetLatestChannelEntriesThatProvide",
+        "GetLatestProvidersRequest",
+        "ChannelEntry",
+        true,
+    ),
+    (
+        "get_default_bundle_that_provides",
+        "GetDefaultBundleThatProvides",
+        "GetDefaultProviderRequest",
+        "Bundle",
+        false,
+    ),
+    (
+        "list_bundles",
+        "ListBundles",
+        "ListBundlesRequest",
+        "Bundle",
+        true,
+    ),
+];
+
+fn main() {
+    let mut service = Service::builder().name("Registry").package("api");
+    for (name, route, input, output, streaming) in METHODS.iter() {
+        let mut method = Method::builder()
+            .name(*name)
+            .route_name(*route)
+            .input_type(format!("crate::registry::model::{}", input))
+            .output_type(format!("crate::registry::model::{}", output))
+            .codec_path("tonic::codec::ProstCodec");
+        if *streaming {
+            method = method.server_streaming();
+        }
+        service = service.method(method.build());
+    }
+    Builder::new()
+        .build_client(false)
+        .compile(&[service.build()]);
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -1,5 +1,8 @@
 // module api
+use crate::isc::generate::{ISC_API_VERSIONS, ISC_STYLES};
+use crate::upgradepath::graph::GRAPH_FORMATS;
 use clap::{Parser, Subcommand};
+use regex::Regex;
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
 
@@ -52,6 +55,13 @@ pub enum Commands {
             help = "Filter the list with a specific operator"
         )]
         operator: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "wait",
+            help = "Wait for the working-dir lock to be released instead of failing when held by another process"
+        )]
+        wait: bool,
     },
     /// Update subcommand (fetches the latest catalog from RedHat registry)
     Update {
@@ -66,6 +76,35 @@ pub enum Commands {
             help = "Sets the working-dir, used to share existing caches with other catalog tooling"
         )]
         working_dir: String,
+
+        #[arg(
+            long,
+            value_name = "cache-mode",
+            default_value = "755",
+            help = "Octal mode for the untarred cache directories, files use the same mode without execute bits (the process umask is applied, world writable modes are rejected)"
+        )]
+        cache_mode: String,
+
+        #[arg(
+            long,
+            value_name = "cache-owner",
+            help = "Numeric uid:gid set on the untarred cache (by default the ownership is left as extracted)"
+        )]
+        cache_owner: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "read-only-cache",
+            help = "Make the untarred cache read-only once extraction has completed"
+        )]
+        read_only_cache: bool,
+
+        #[arg(
+            long,
+            value_name = "wait",
+            help = "Wait for the working-dir lock to be released instead of failing when held by another process"
+        )]
+        wait: bool,
     },
     /// Upgradepath subcommand (calculates an upgradepath on the given filterconfig and generates
     /// an imagesetconfig)
@@ -87,6 +126,7 @@ pub enum Commands {
             long,
             value_name = "api-version",
             default_value = "v2alpha1",
+            value_parser = ISC_API_VERSIONS,
             help = "Sets the api version when generating the imagesetconfig"
         )]
         api_version: String,
@@ -99,6 +139,259 @@ pub enum Commands {
             help = "The directory to output the auto-generated imagesetconfig to"
         )]
         output_dir: String,
+
+        #[arg(
+            long,
+            value_name = "output-file",
+            conflicts_with = "per_catalog",
+            help = "The file name of the auto-generated imagesetconfig in the output-dir [default: auto-generated.yaml]"
+        )]
+        output_file: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "merge-into",
+            conflicts_with_all = ["output_file", "per_catalog"],
+            help = "Merge the computed operators into an existing imagesetconfig (the file is updated in place with --force, other sections are preserved but not yaml comments)"
+        )]
+        merge_into: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "per-catalog",
+            help = "Write one imagesetconfig per catalog (named after the catalog and tag)"
+        )]
+        per_catalog: bool,
+
+        #[arg(
+            long,
+            value_name = "stdout",
+            help = "Print the imagesetconfig to stdout instead of writing it to the output-dir"
+        )]
+        stdout: bool,
+
+        #[arg(
+            long,
+            value_name = "force",
+            help = "Overwrite existing imagesetconfig files"
+        )]
+        force: bool,
+
+        #[arg(
+            long,
+            value_name = "isc-style",
+            default_value = "bundles",
+            value_parser = ISC_STYLES,
+            help = "How packages are expressed in the imagesetconfig, bundles (list of bundles), channels (channel with minVersion/maxVersion) or versions (package minVersion/maxVersion)"
+        )]
+        isc_style: String,
+
+        #[arg(
+            long,
+            value_name = "strict",
+            help = "Fail when a requested package is missing from one of its targeted catalogs, has an empty upgrade path or a dependency is unresolved"
+        )]
+        strict: bool,
+
+        #[arg(
+            long,
+            value_name = "skip-dependencies",
+            help = "Do not add the packages required (olm.package.required, olm.gvk.required) by the selected bundles"
+        )]
+        skip_dependencies: bool,
+
+        #[arg(
+            long,
+            value_name = "target-ocp",
+            help = "Report the bundles on the upgrade path blocking a cluster upgrade to this openshift version (olm.maxOpenShiftVersion) i.e 4.16"
+        )]
+        target_ocp: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "exclude-deprecated",
+            help = "Leave deprecated packages and bundles (olm.deprecations) out of the generated imagesetconfig"
+        )]
+        exclude_deprecated: bool,
+
+        #[arg(
+            long,
+            value_name = "graph",
+            value_parser = GRAPH_FORMATS,
+            help = "Export the upgrade graph of each package channel (saved in <output-dir>/graphs) [possible values: dot, mermaid]"
+        )]
+        graph: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "related-images",
+            help = "Extract the related images of the selected bundles (saved as related-images.txt and related-images.json in the output-dir)"
+        )]
+        related_images: bool,
+
+        #[arg(
+            long,
+            value_name = "estimate-size",
+            help = "Estimate the mirror size of the selected bundles (resolves the image manifests from the registries)"
+        )]
+        estimate_size: bool,
+
+        #[arg(
+            long,
+            value_name = "wait",
+            help = "Wait for the working-dir lock to be released instead of failing when held by another process"
+        )]
+        wait: bool,
+    },
+    /// Search subcommand (searches packages across all cached catalogs)
+    Search {
+        #[arg(
+            short,
+            long,
+            value_name = "working-dir",
+            help = "The directory where all indexes have been downloaded (required)"
+        )]
+        working_dir: String,
+
+        #[arg(
+            short,
+            long,
+            value_name = "query",
+            help = "Case insensitive text matched against package names, display names, descriptions, keywords and provider (required)"
+        )]
+        query: String,
+
+        #[arg(
+            long,
+            value_name = "wait",
+            help = "Wait for the working-dir lock to be released instead of failing when held by another process"
+        )]
+        wait: bool,
+    },
+    /// Info subcommand (displays the operator details from the head bundle metadata)
+    Info {
+        #[arg(
+            short,
+            long,
+            value_name = "working-dir",
+            help = "The directory where all indexes have been downloaded (required)"
+        )]
+        working_dir: String,
+
+        #[arg(
+            short,
+            long,
+            value_name = "catalog",
+            help = "The catalog the operator belongs to i.e redhat-operator-index:v4.15 (required)"
+        )]
+        catalog: String,
+
+        #[arg(
+            short,
+            long,
+            value_name = "operator",
+            help = "The operator (package) name (required)"
+        )]
+        operator: String,
+
+        #[arg(
+            long,
+            value_name = "channel",
+            help = "Use the head bundle of this channel [default: the package default channel]"
+        )]
+        channel: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "wait",
+            help = "Wait for the working-dir lock to be released instead of failing when held by another process"
+        )]
+        wait: bool,
+    },
+    /// Browse subcommand (interactive terminal browser for the cached catalogs)
+    Browse {
+        #[arg(
+            short,
+            long,
+            value_name = "working-dir",
+            help = "The directory where all indexes have been downloaded (required)"
+        )]
+        working_dir: String,
+
+        #[arg(
+            short,
+            long,
+            value_name = "output-dir",
+            default_value = ".",
+            help = "The directory where the marked packages are exported (browse-filter.yaml, browse-isc.yaml)"
+        )]
+        output_dir: String,
+
+        #[arg(
+            long,
+            value_name = "wait",
+            help = "Wait for the working-dir lock to be released instead of failing when held by another process"
+        )]
+        wait: bool,
+    },
+    /// Serve subcommand (read-only http/json api over the cached catalogs)
+    Serve {
+        #[arg(
+            short,
+            long,
+            value_name = "working-dir",
+            help = "The directory where all indexes have been downloaded (required)"
+        )]
+        working_dir: String,
+
+        #[arg(
+            short,
+            long,
+            value_name = "address",
+            default_value = "127.0.0.1:8080",
+            help = "The address (host:port) the api listens on"
+        )]
+        address: String,
+    },
+    /// Registry subcommand (serves a cached catalog over the OLM registry grpc api)
+    Registry {
+        #[arg(
+            short,
+            long,
+            value_name = "working-dir",
+            help = "The directory where all indexes have been downloaded (required)"
+        )]
+        working_dir: String,
+
+        #[arg(
+            short,
+            long,
+            value_name = "catalog",
+            help = "The cached catalog to serve i.e redhat-operator-index:v4.15 (required)"
+        )]
+        catalog: String,
+
+        #[arg(
+            short,
+            long,
+            value_name = "address",
+            default_value = "0.0.0.0:50051",
+            help = "The address (host:port) the grpc api listens on"
+        )]
+        address: String,
+
+        #[arg(
+            long,
+            value_name = "wait",
+            help = "Wait for the working-dir lock to be released instead of failing when held by another process"
+        )]
+        wait: bool,
+    },
+    /// Validate subcommand (lints filter config files, useful in CI)
+    Validate {
+        /// config file(s) to validate
+        #[arg(short, long, value_name = "config_file", required = true)]
+        config_file: Vec<String>,
     },
 }
 
@@ -116,16 +409,220 @@ pub struct FilterConfig {
 
     #[serde(rename = "packages")]
     pub operators: Option<Vec<FilterOperator>>,
+
+    #[serde(rename = "platform", skip_serializing_if = "Option::is_none")]
+    pub platform: Option<FilterPlatform>,
+
+    #[serde(rename = "additionalImages", skip_serializing_if = "Option::is_none")]
+    pub additional_images: Option<Vec<FilterImage>>,
 }
 
+/// platform (release) section, the channels are derived from the catalog versions
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FilterPlatform {
+    /// channel prefix i.e stable, fast, eus, candidate (defaults to stable)
+    #[serde(rename = "channel")]
+    pub channel: Option<String>,
+
+    #[serde(rename = "minVersion")]
+    pub min_version: Option<String>,
+
+    #[serde(rename = "maxVersion")]
+    pub max_version: Option<String>,
+
+    #[serde(rename = "graph")]
+    pub graph: Option<bool>,
+
+    #[serde(rename = "architectures")]
+    pub architectures: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FilterImage {
+    #[serde(rename = "name")]
+    pub name: String,
+}
+
+// prefix used for regular expressions in package names
+pub const REGEX_PREFIX: &str = "regex:";
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FilterOperator {
+    /// package name, glob (i.e rhpam-*) or regular expression matching the whole name (i.e regex:rhpam-.*)
     #[serde(rename = "name")]
     pub name: String,
 
-    #[serde(rename = "channel")]
+    #[serde(rename = "channel", skip_serializing_if = "Option::is_none")]
     pub channel: Option<String>,
 
-    #[serde(rename = "fromVersion")]
+    #[serde(rename = "fromVersion", skip_serializing_if = "Option::is_none")]
     pub from_version: Option<String>,
+
+    /// restrict the package to these catalogs (full reference or index name i.e certified-operator-index)
+    #[serde(rename = "catalogs", default, skip_serializing_if = "Option::is_none")]
+    pub catalogs: Option<Vec<String>>,
+}
+
+impl FilterOperator {
+    // check if the name is a pattern, globs (i.e rhpam-*) or regular expressions (i.e regex:rhpam-.*)
+    pub fn is_pattern(&self) -> bool {
+        self.name.starts_with(REGEX_PREFIX) || self.name.contains(['*', '?', '['])
+    }
+
+    // the name as an anchored regular expression (the whole package name must match)
+    // glob character classes are kept, [!x] is negated like [^x]
+    pub fn pattern(&self) -> Result<Regex, regex::Error> {
+        if self.name.starts_with(REGEX_PREFIX) {
+            return Regex::new(&format!(
+                "^(?:{})$",
+                self.name.trim_start_matches(REGEX_PREFIX)
+            ));
+        }
+        let mut re = String::from("^");
+        let mut in_class = false;
+        let mut chars = self.name.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, in_class) {
+                ('[', false) => {
+                    in_class = true;
+                    re.push('[');
+                    if chars.peek() == Some(&'!') {
+                        chars.next();
+                        re.push('^');
+                    }
+                }
+                (']', true) => {
+                    in_class = false;
+                    re.push(']');
+                }
+                ('-', true) => re.push('-'),
+                ('*', false) => re.push_str(".*"),
+                ('?', false) => re.push('.'),
+                _ => re.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        re.push('$');
+        Regex::new(&re)
+    }
+
+    // exact package name match, or pattern match when the name is a pattern
+    pub fn matches_package(&self, package: &str) -> bool {
+        if !self.is_pattern() {
+            return self.name == package;
+        }
+        match self.pattern() {
+            Ok(re) => re.is_match(package),
+            Err(_) => false,
+        }
+    }
+
+    // check if the package should be evaluated against the given catalog
+    pub fn targets_catalog(&self, catalog: &str) -> bool {
+        if self.catalogs.is_none() {
+            return true;
+        }
+        let last = catalog.split('/').last().unwrap();
+        let index = last.split(':').nth(0).unwrap().split('@').nth(0).unwrap();
+        let repository = catalog.trim_end_matches(last).to_string() + index;
+        self.catalogs
+            .as_ref()
+            .unwrap()
+            .iter()
+            .any(|c| c == catalog || c == last || c == index || c == &repository)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+
+    fn operator(name: &str, catalogs: Option<Vec<&str>>) -> FilterOperator {
+        FilterOperator {
+            name: name.to_string(),
+            channel: None,
+            from_version: None,
+            catalogs: catalogs.map(|c| c.iter().map(|x| x.to_string()).collect()),
+        }
+    }
+
+    #[test]
+    fn matches_package_pass() {
+        // exact names are not patterns
+        let op = operator("rhpam-kogito-operator", None);
+        assert_eq!(op.is_pattern(), false);
+        assert_eq!(op.matches_package("rhpam-kogito-operator"), true);
+        assert_eq!(op.matches_package("rhpam-kogito-operator-v2"), false);
+
+        // globs match the whole name
+        let op = operator("rhpam-*", None);
+        assert_eq!(op.is_pattern(), true);
+        assert_eq!(op.pattern().unwrap().as_str(), "^rhpam\\-.*$");
+        assert_eq!(op.matches_package("rhpam-kogito-operator"), true);
+        assert_eq!(op.matches_package("my-rhpam-operator"), false);
+        let op = operator("amq?streams", None);
+        assert_eq!(op.matches_package("amq-streams"), true);
+        assert_eq!(op.matches_package("amq--streams"), false);
+
+        // character classes, [!x] is negated
+        let op = operator("serverless-operator-v[0-9]", None);
+        assert_eq!(op.matches_package("serverless-operator-v1"), true);
+        assert_eq!(op.matches_package("serverless-operator-vx"), false);
+        let op = operator("[!a]mq-streams", None);
+        assert_eq!(op.pattern().unwrap().as_str(), "^[^a]mq\\-streams$");
+        assert_eq!(op.matches_package("amq-streams"), false);
+        assert_eq!(op.matches_package("xmq-streams"), true);
+
+        // regular expressions are anchored
+        let op = operator("regex:rhpam-.*", None);
+        assert_eq!(op.is_pattern(), true);
+        assert_eq!(op.matches_package("rhpam-kogito-operator"), true);
+        assert_eq!(op.matches_package("my-rhpam-operator"), false);
+        let op = operator("regex:kogito|jaeger-product", None);
+        assert_eq!(op.matches_package("jaeger-product"), true);
+        assert_eq!(op.matches_package("rhpam-kogito-operator"), false);
+
+        // invalid patterns don't match anything
+        for name in ["regex:rhpam-(", "rhpam-[a"] {
+            let op = operator(name, None);
+            assert_eq!(op.pattern().is_err(), true);
+            assert_eq!(op.matches_package("rhpam-kogito-operator"), false);
+        }
+    }
+
+    #[test]
+    fn targets_catalog_pass() {
+        let catalog = "registry.redhat.io/redhat/redhat-operator-index:v4.15";
+        // no catalogs targets all of them
+        assert_eq!(
+            operator("jaeger-product", None).targets_catalog(catalog),
+            true
+        );
+        // full reference, name with tag, index name and repository
+        for target in [
+            "registry.redhat.io/redhat/redhat-operator-index:v4.15",
+            "redhat-operator-index:v4.15",
+            "redhat-operator-index",
+            "registry.redhat.io/redhat/redhat-operator-index",
+        ] {
+            let op = operator("jaeger-product", Some(vec![target]));
+            assert_eq!(op.targets_catalog(catalog), true);
+        }
+        // other tags, other indexes and partial names don't match
+        for target in [
+            "redhat-operator-index:v4.14",
+            "certified-operator-index",
+            "redhat-operator",
+            "registry.redhat.io/redhat",
+        ] {
+            let op = operator("jaeger-product", Some(vec![target]));
+            assert_eq!(op.targets_catalog(catalog), false);
+        }
+        // digests
+        let op = operator("jaeger-product", Some(vec!["redhat-operator-index"]));
+        assert_eq!(
+            op.targets_catalog("registry.redhat.io/redhat/redhat-operator-index@sha256:1234"),
+            true
+        );
+    }
 }
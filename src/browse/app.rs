@@ -0,0 +1,568 @@
+use crate::api::schema::*;
+use crate::fbc::reader::*;
+use crate::isc::generate::*;
+use crate::upgradepath::calculate::*;
+use mirror_error::MirrorError;
+use ratatui::crossterm::event::KeyCode;
+use std::fs;
+
+pub const BROWSE_FILTER_FILE: &str = "browse-filter.yaml";
+pub const BROWSE_ISC_FILE: &str = "browse-isc.yaml";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum View {
+    Catalogs,
+    Packages,
+    Package,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Focus {
+    Channels,
+    Bundles,
+}
+
+// a package marked for export
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mark {
+    pub catalog: String,
+    pub package: String,
+    pub channel: Option<String>,
+    pub from_version: Option<String>,
+    pub path: Vec<String>,
+}
+
+// the opened package (channels, bundles and the upgrade path from the selected bundle)
+#[derive(Debug, Clone, Default)]
+pub struct PackageView {
+    pub name: String,
+    pub default_channel: String,
+    pub channels: Vec<OlmChannel>,
+    pub deprecations: OlmDeprecations,
+    pub channel_idx: usize,
+    pub bundle_idx: usize,
+    pub from: Option<String>,
+    pub path: Vec<String>,
+}
+
+impl PackageView {
+    pub fn channel(&self) -> Option<&OlmChannel> {
+        self.channels.get(self.channel_idx)
+    }
+
+    // the channel entries sorted by version
+    pub fn bundles(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .channel()
+            .map(|c| c.entries.iter().map(|e| e.name.clone()).collect())
+            .unwrap_or(vec![]);
+        names.sort_by_key(|n| semver::Version::parse(&bundle_version(n)).ok());
+        names
+    }
+}
+
+pub struct App {
+    pub dir: String,
+    pub output_dir: String,
+    pub view: View,
+    pub focus: Focus,
+    // (name:version, cache directory)
+    pub catalogs: Vec<(String, String)>,
+    pub catalog_idx: usize,
+    pub config_dir: String,
+    pub packages: Vec<String>,
+    pub filter: String,
+    pub filtering: bool,
+    pub package_idx: usize,
+    pub detail: Option<PackageView>,
+    pub marks: Vec<Mark>,
+    pub status: String,
+    pub quit: bool,
+}
+
+impl App {
+    pub fn new(dir: String, output_dir: String) -> Self {
+        let catalogs = list_cached_catalogs(dir.clone());
+        let status = if catalogs.len() == 0 {
+            format!("no cached catalogs found in {} (execute update first)", dir)
+        } else {
+            String::from("")
+        };
+        App {
+            dir,
+            output_dir,
+            view: View::Catalogs,
+            focus: Focus::Channels,
+            catalogs,
+            catalog_idx: 0,
+            config_dir: String::from(""),
+            packages: vec![],
+            filter: String::from(""),
+            filtering: false,
+            package_idx: 0,
+            detail: None,
+            marks: vec![],
+            status,
+            quit: false,
+        }
+    }
+
+    pub fn catalog(&self) -> String {
+        self.catalogs
+            .get(self.catalog_idx)
+            .map(|c| c.0.clone())
+            .unwrap_or_default()
+    }
+
+    // packages matching the filter (substring, case insensitive)
+    pub fn filtered_packages(&self) -> Vec<String> {
+        let filter = self.filter.to_lowercase();
+        self.packages
+            .iter()
+            .filter(|p| p.to_lowercase().contains(&filter))
+            .cloned()
+            .collect()
+    }
+
+    pub fn is_marked(&self, package: &str) -> bool {
+        let catalog = self.catalog();
+        self.marks
+            .iter()
+            .any(|m| m.catalog == catalog && m.package == package)
+    }
+
+    pub fn on_key(&mut self, key: KeyCode) {
+        if self.filtering {
+            match key {
+                KeyCode::Char(c) => self.filter.push(c),
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                }
+                KeyCode::Enter | KeyCode::Esc => self.filtering = false,
+                _ => {}
+            }
+            self.package_idx = 0;
+            return;
+        }
+        match key {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Enter => self.open(),
+            KeyCode::Esc | KeyCode::Backspace => self.back(),
+            KeyCode::Tab | KeyCode::Left | KeyCode::Right => {
+                if self.view == View::Package {
+                    self.focus = match self.focus {
+                        Focus::Channels => Focus::Bundles,
+                        Focus::Bundles => Focus::Channels,
+                    };
+                }
+            }
+            KeyCode::Char('/') => {
+                if self.view == View::Packages {
+                    self.filtering = true;
+                }
+            }
+            KeyCode::Char(' ') => self.toggle_mark(),
+            KeyCode::Char('f') => {
+                self.status = match self.export_filter() {
+                    Ok(f) => format!("filter config saved to {}", f),
+                    Err(e) => e.to_string(),
+                }
+            }
+            KeyCode::Char('i') => {
+                self.status = match self.export_isc() {
+                    Ok(f) => format!("imagesetconfig saved to {}", f),
+                    Err(e) => e.to_string(),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn move_selection(&mut self, delta: i64) {
+        let step = |idx: usize, len: usize| -> usize {
+            if len == 0 {
+                return 0;
+            }
+            (idx as i64 + delta).clamp(0, len as i64 - 1) as usize
+        };
+        match self.view {
+            View::Catalogs => self.catalog_idx = step(self.catalog_idx, self.catalogs.len()),
+            View::Packages => {
+                self.package_idx = step(self.package_idx, self.filtered_packages().len())
+            }
+            View::Package => {
+                let focus = self.focus.clone();
+                let detail = self.detail.as_mut().unwrap();
+                match focus {
+                    Focus::Channels => {
+                        detail.channel_idx = step(detail.channel_idx, detail.channels.len());
+                        detail.bundle_idx = 0;
+                        detail.from = None;
+                        detail.path = vec![];
+                    }
+                    Focus::Bundles => {
+                        detail.bundle_idx = step(detail.bundle_idx, detail.bundles().len())
+                    }
+                }
+            }
+        }
+    }
+
+    fn open(&mut self) {
+        match self.view {
+            View::Catalogs => {
+                if self.catalogs.len() == 0 {
+                    return;
+                }
+                let cache_dir = self.catalogs[self.catalog_idx].1.clone();
+                match find_configs_dir(&cache_dir) {
+                    Some(d) => {
+                        self.packages = list_packages(d.clone());
+                        self.config_dir = d;
+                        self.filter = String::from("");
+                        self.package_idx = 0;
+                        self.view = View::Packages;
+                        self.status = format!("{} packages", self.packages.len());
+                    }
+                    None => self.status = format!("no 'configs' directory found in {}", cache_dir),
+                }
+            }
+            View::Packages => {
+                let packages = self.filtered_packages();
+                if let Some(name) = packages.get(self.package_idx) {
+                    match self.load_package(name) {
+                        Ok(detail) => {
+                            self.status = match detail.deprecations.package() {
+                                Some(msg) => format!("deprecated : {}", msg.trim()),
+                                None => String::from(""),
+                            };
+                            self.detail = Some(detail);
+                            self.focus = Focus::Channels;
+                            self.view = View::Package;
+                        }
+                        Err(e) => self.status = e.to_string(),
+                    }
+                }
+            }
+            View::Package => {
+                // select the from version, the path is calculated from the selected bundle
+                if self.focus == Focus::Channels {
+                    self.focus = Focus::Bundles;
+                    return;
+                }
+                let detail = self.detail.as_mut().unwrap();
+                let bundles = detail.bundles();
+                if let Some(from) = bundles.get(detail.bundle_idx) {
+                    match channel_upgrade_path(detail.channel().unwrap(), &bundle_version(from)) {
+                        Ok(path) => {
+                            detail.path = path;
+                            detail.from = Some(from.clone());
+                        }
+                        Err(e) => self.status = e.to_string(),
+                    }
+                }
+            }
+        }
+    }
+
+    fn back(&mut self) {
+        self.view = match self.view {
+            View::Package => View::Packages,
+            _ => View::Catalogs,
+        };
+    }
+
+    pub fn load_package(&self, name: &String) -> Result<PackageView, MirrorError> {
+        let blobs = read_package_blobs(self.config_dir.clone(), name.clone())?;
+        let default_channel = get_package(&blobs)
+            .and_then(|p| p.default_channel)
+            .unwrap_or_default();
+        let mut channels = get_channels(&blobs);
+        channels.sort_by(|a, b| a.name.cmp(&b.name));
+        let channel_idx = channels
+            .iter()
+            .position(|c| c.name == default_channel)
+            .unwrap_or(0);
+        Ok(PackageView {
+            name: name.clone(),
+            default_channel,
+            channels,
+            deprecations: get_deprecations(&blobs),
+            channel_idx,
+            ..Default::default()
+        })
+    }
+
+    // mark (or unmark) the selected package, in the package view the channel
+    // and from version are kept
+    fn toggle_mark(&mut self) {
+        let catalog = self.catalog();
+        let mark = match self.view {
+            View::Catalogs => return,
+            View::Packages => {
+                let packages = self.filtered_packages();
+                let name = match packages.get(self.package_idx) {
+                    Some(n) => n.clone(),
+                    None => return,
+                };
+                if self.is_marked(&name) {
+                    self.unmark(&catalog, &name);
+                    return;
+                }
+                // the head of the default channel
+                let detail = match self.load_package(&name) {
+                    Ok(d) => d,
+                    Err(e) => {
+                        self.status = e.to_string();
+                        return;
+                    }
+                };
+                let path = match head_path(&detail) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        self.status = e.to_string();
+                        return;
+                    }
+                };
+                Mark {
+                    catalog: catalog.clone(),
+                    package: name.clone(),
+                    channel: None,
+                    from_version: None,
+                    path,
+                }
+            }
+            View::Package => {
+                let detail = self.detail.as_ref().unwrap();
+                let name = detail.name.clone();
+                if self.is_marked(&name) {
+                    self.unmark(&catalog, &name);
+                    return;
+                }
+                let channel = detail.channel().map(|c| c.name.clone());
+                let path = match &detail.from {
+                    Some(_) => detail.path.clone(),
+                    None => match head_path(detail) {
+                        Ok(p) => p,
+                        Err(e) => {
+                            self.status = e.to_string();
+                            return;
+                        }
+                    },
+                };
+                Mark {
+                    catalog: catalog.clone(),
+                    package: name,
+                    channel: channel.filter(|c| c != &detail.default_channel),
+                    from_version: detail.from.as_ref().map(bundle_version),
+                    path,
+                }
+            }
+        };
+        self.status = format!("marked {} ({} marked)", mark.package, self.marks.len() + 1);
+        self.marks.push(mark);
+    }
+
+    fn unmark(&mut self, catalog: &String, package: &String) {
+        self.marks
+            .retain(|m| !(&m.catalog == catalog && &m.package == package));
+        self.status = format!("unmarked {} ({} marked)", package, self.marks.len());
+    }
+
+    // the full reference recorded by update, the cached name for older caches
+    pub fn catalog_reference(&self, catalog: &String) -> String {
+        self.catalogs
+            .iter()
+            .find(|c| &c.0 == catalog)
+            .and_then(|c| cached_catalog_reference(&c.1))
+            .unwrap_or(catalog.clone())
+    }
+
+    // the marked packages as a filter config (usable with upgradepath)
+    pub fn to_filter_config(&self) -> FilterConfig {
+        let mut catalogs: Vec<String> = vec![];
+        for m in self.marks.iter() {
+            let reference = self.catalog_reference(&m.catalog);
+            if !catalogs.contains(&reference) {
+                catalogs.push(reference);
+            }
+        }
+        let operators = self
+            .marks
+            .iter()
+            .map(|m| FilterOperator {
+                name: m.package.clone(),
+                channel: m.channel.clone(),
+                from_version: m.from_version.clone(),
+                catalogs: if catalogs.len() > 1 {
+                    Some(vec![self.catalog_reference(&m.catalog)])
+                } else {
+                    None
+                },
+            })
+            .collect();
+        FilterConfig {
+            kind: "FilterConfiguration".to_string(),
+            api_version: "mirror.openshift.io/v1alpha2".to_string(),
+            catalogs,
+            operators: Some(operators),
+            platform: None,
+            additional_images: None,
+        }
+    }
+
+    // the marked packages as an imagesetconfig (the bundles of the upgrade path)
+    pub fn to_isc(&self) -> IscConfig {
+        let mut isc = IscConfig::new(ISC_API_VERSIONS[1].to_string());
+        for m in self.marks.iter() {
+            let reference = self.catalog_reference(&m.catalog);
+            let idx = match isc.operators.iter().position(|c| c.catalog == reference) {
+                Some(i) => i,
+                None => {
+                    isc.operators.push(Catalog {
+                        catalog: reference,
+                        packages: vec![],
+                    });
+                    isc.operators.len() - 1
+                }
+            };
+            isc.operators[idx].packages.push(Package {
+                name: m.package.clone(),
+                default_channel: m.channel.clone(),
+                bundles: m.path.iter().map(|b| Bundle { name: b.clone() }).collect(),
+                ..Default::default()
+            });
+        }
+        isc
+    }
+
+    pub fn export_filter(&self) -> Result<String, MirrorError> {
+        if self.marks.len() == 0 {
+            return Err(MirrorError::new(
+                "[export_filter] no packages marked (use space)",
+            ));
+        }
+        let res = serde_yaml::to_string(&self.to_filter_config());
+        if res.is_err() {
+            return Err(MirrorError::new(&format!(
+                "[export_filter] {}",
+                res.err().unwrap().to_string().to_lowercase()
+            )));
+        }
+        self.write_file(BROWSE_FILTER_FILE, res.unwrap())
+    }
+
+    pub fn export_isc(&self) -> Result<String, MirrorError> {
+        if self.marks.len() == 0 {
+            return Err(MirrorError::new(
+                "[export_isc] no packages marked (use space)",
+            ));
+        }
+        self.write_file(BROWSE_ISC_FILE, self.to_isc().to_yaml())
+    }
+
+    fn write_file(&self, name: &str, contents: String) -> Result<String, MirrorError> {
+        let file = format!("{}/{}", self.output_dir, name);
+        let res = fs::create_dir_all(&self.output_dir).and_then(|_| fs::write(&file, contents));
+        if res.is_err() {
+            return Err(MirrorError::new(&format!(
+                "[write_file] writing {} {}",
+                file,
+                res.err().unwrap().to_string().to_lowercase()
+            )));
+        }
+        Ok(file)
+    }
+}
+
+// the upgrade path of the selected channel from the first bundle
+fn head_path(detail: &PackageView) -> Result<Vec<String>, MirrorError> {
+    match detail.channel() {
+        Some(c) => channel_upgrade_path(c, &"0.0.0".to_string()),
+        None => Ok(vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+
+    #[test]
+    fn browse_app_pass() {
+        let dir = "./test-artifacts/browse".to_string();
+        let config_dir = format!(
+            "{}/test-index/v0.0.1/amd64/cache/layer/configs/test-operator",
+            dir
+        );
+        fs::create_dir_all(&config_dir).expect("should create browse test folder");
+        fs::write(
+            format!("{}/catalog.json", config_dir),
+            "{ \"schema\": \"olm.package\", \"name\": \"test-operator\", \"defaultChannel\": \"stable\" }
+            { \"schema\": \"olm.channel\", \"name\": \"stable\", \"package\": \"test-operator\",
+              \"entries\": [
+                { \"name\": \"test-operator.v0.0.1\" },
+                { \"name\": \"test-operator.v0.0.2\", \"replaces\": \"test-operator.v0.0.1\" },
+                { \"name\": \"test-operator.v0.0.3\", \"replaces\": \"test-operator.v0.0.2\" } ] }",
+        )
+        .expect("should write catalog");
+
+        let mut app = App::new(dir.clone(), dir.clone());
+        assert_eq!(app.catalogs.len(), 1);
+        assert_eq!(app.catalog(), "test-index:v0.0.1");
+
+        // catalog -> packages (filtered) -> package
+        app.on_key(KeyCode::Enter);
+        assert_eq!(app.view, View::Packages);
+        assert_eq!(app.packages, vec!["test-operator"]);
+        app.on_key(KeyCode::Char('/'));
+        for c in "nomatch".chars() {
+            app.on_key(KeyCode::Char(c));
+        }
+        assert_eq!(app.filtered_packages().len(), 0);
+        app.on_key(KeyCode::Esc);
+        app.filter = "test".to_string();
+        app.on_key(KeyCode::Enter);
+        assert_eq!(app.view, View::Package);
+        assert_eq!(app.detail.as_ref().unwrap().default_channel, "stable");
+
+        // select the from version and calculate the upgrade path
+        app.on_key(KeyCode::Tab);
+        app.on_key(KeyCode::Down);
+        app.on_key(KeyCode::Enter);
+        let detail = app.detail.as_ref().unwrap();
+        assert_eq!(detail.from, Some("test-operator.v0.0.2".to_string()));
+        assert_eq!(detail.path, vec!["test-operator.v0.0.3"]);
+
+        app.on_key(KeyCode::Char(' '));
+        assert_eq!(app.marks.len(), 1);
+        assert_eq!(app.marks[0].from_version, Some("0.0.2".to_string()));
+
+        app.on_key(KeyCode::Char('f'));
+        let filter = fs::read_to_string(format!("{}/{}", dir, BROWSE_FILTER_FILE))
+            .expect("should read filter config");
+        assert!(filter.contains("- test-index:v0.0.1"));
+        // the full reference recorded by update is exported
+        save_catalog_reference(dir.clone(), "quay.io/test/test-index:v0.0.1".to_string())
+            .expect("should save catalog reference");
+        app.on_key(KeyCode::Char('f'));
+        let filter = fs::read_to_string(format!("{}/{}", dir, BROWSE_FILTER_FILE))
+            .expect("should read filter config");
+        assert!(filter.contains("quay.io/test/test-index:v0.0.1"));
+        assert!(filter.contains("fromVersion: 0.0.2"));
+        app.on_key(KeyCode::Char('i'));
+        let isc =
+            fs::read_to_string(format!("{}/{}", dir, BROWSE_ISC_FILE)).expect("should read isc");
+        assert!(isc.contains("name: test-operator.v0.0.3"));
+        assert!(isc.contains("catalog: quay.io/test/test-index:v0.0.1"));
+
+        // unmark from the packages view
+        app.on_key(KeyCode::Esc);
+        app.on_key(KeyCode::Char(' '));
+        assert_eq!(app.marks.len(), 0);
+
+        fs::remove_dir_all(&dir).expect("should delete browse test folder");
+    }
+}
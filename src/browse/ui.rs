@@ -0,0 +1,241 @@
+use crate::browse::app::*;
+use custom_logger::*;
+use mirror_error::MirrorError;
+use ratatui::crossterm::event::{self, Event, KeyEventKind};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use std::io::stdout;
+
+const HELP: &str = "↑/↓ move  enter open/select  esc back  tab channels/bundles  / filter  space mark  f export filter  i export isc  q quit";
+
+// run the terminal browser until the user quits
+pub fn run_browse(log: &Logging, dir: String, output_dir: String) -> Result<(), MirrorError> {
+    let mut app = App::new(dir, output_dir);
+    log.debug(&format!(
+        "[run_browse] {} cached catalogs",
+        app.catalogs.len()
+    ));
+
+    // always restore the terminal, even on panic
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        hook(info);
+    }));
+    let res = enable_raw_mode().and_then(|_| execute!(stdout(), EnterAlternateScreen));
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[run_browse] setting up terminal {}",
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    let res = event_loop(&mut app);
+    let _ = restore_terminal();
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[run_browse] {}",
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    if app.marks.len() > 0 {
+        log.info(&format!(
+            "[run_browse] {} package(s) marked",
+            app.marks.len()
+        ));
+    }
+    Ok(())
+}
+
+fn event_loop(app: &mut App) -> std::io::Result<()> {
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    while !app.quit {
+        terminal.draw(|f| draw(f, app))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                app.on_key(key.code);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn restore_terminal() -> std::io::Result<()> {
+    disable_raw_mode()?;
+    execute!(stdout(), LeaveAlternateScreen)
+}
+
+fn draw(f: &mut Frame, app: &App) {
+    let chunks = Layout::vertical([
+        Constraint::Min(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .split(f.area());
+    match app.view {
+        View::Catalogs => draw_catalogs(f, app, chunks[0]),
+        View::Packages => draw_packages(f, app, chunks[0]),
+        View::Package => draw_package(f, app, chunks[0]),
+    }
+    f.render_widget(
+        Paragraph::new(app.status.clone()).style(Style::new().yellow()),
+        chunks[1],
+    );
+    f.render_widget(
+        Paragraph::new(HELP).style(Style::new().dark_gray()),
+        chunks[2],
+    );
+}
+
+fn selectable_list<'a>(items: Vec<ListItem<'a>>, title: String, active: bool) -> List<'a> {
+    let block = if active {
+        Block::bordered()
+            .title(title)
+            .border_style(Style::new().cyan())
+    } else {
+        Block::bordered().title(title)
+    };
+    List::new(items)
+        .block(block)
+        .highlight_style(Style::new().reversed())
+}
+
+fn draw_catalogs(f: &mut Frame, app: &App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .catalogs
+        .iter()
+        .map(|(name, _)| ListItem::new(name.clone()))
+        .collect();
+    let list = selectable_list(items, format!(" catalogs ({}) ", app.dir), true);
+    let mut state = ListState::default().with_selected(Some(app.catalog_idx));
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_packages(f: &mut Frame, app: &App, area: Rect) {
+    let chunks = Layout::vertical([Constraint::Length(3), Constraint::Min(1)]).split(area);
+    let filter_style = if app.filtering {
+        Style::new().cyan()
+    } else {
+        Style::new()
+    };
+    f.render_widget(
+        Paragraph::new(app.filter.clone()).block(
+            Block::bordered()
+                .title(" filter (/) ")
+                .border_style(filter_style),
+        ),
+        chunks[0],
+    );
+    let packages = app.filtered_packages();
+    let items: Vec<ListItem> = packages
+        .iter()
+        .map(|p| {
+            let mark = if app.is_marked(p) { "[x] " } else { "[ ] " };
+            ListItem::new(format!("{}{}", mark, p))
+        })
+        .collect();
+    let title = format!(
+        " {} packages ({}/{}) ",
+        app.catalog(),
+        packages.len(),
+        app.packages.len()
+    );
+    let list = selectable_list(items, title, !app.filtering);
+    let mut state = ListState::default().with_selected(Some(app.package_idx));
+    f.render_stateful_widget(list, chunks[1], &mut state);
+}
+
+fn draw_package(f: &mut Frame, app: &App, area: Rect) {
+    let detail = app.detail.as_ref().unwrap();
+    let chunks = Layout::horizontal([
+        Constraint::Percentage(25),
+        Constraint::Percentage(35),
+        Constraint::Percentage(40),
+    ])
+    .split(area);
+
+    // channels, the default channel in blue and deprecated ones in yellow
+    let items: Vec<ListItem> = detail
+        .channels
+        .iter()
+        .map(|c| {
+            let mut item = ListItem::new(c.name.clone());
+            if detail.deprecations.channel(&c.name).is_some() {
+                item = ListItem::new(format!("{} *", c.name)).style(Style::new().yellow());
+            } else if c.name == detail.default_channel {
+                item = item.style(Style::new().light_blue());
+            }
+            item
+        })
+        .collect();
+    let list = selectable_list(
+        items,
+        format!(" {} channels ", detail.name),
+        app.focus == Focus::Channels,
+    );
+    let mut state = ListState::default().with_selected(Some(detail.channel_idx));
+    f.render_stateful_widget(list, chunks[0], &mut state);
+
+    // bundles of the selected channel
+    let head = detail.channel().and_then(|c| c.head());
+    let items: Vec<ListItem> = detail
+        .bundles()
+        .iter()
+        .map(|b| {
+            let mut text = b.clone();
+            if head.as_ref() == Some(b) {
+                text.push_str(" (head)");
+            }
+            if detail.from.as_ref() == Some(b) {
+                text.push_str(" (from)");
+            }
+            if detail.deprecations.bundle(b).is_some() {
+                return ListItem::new(format!("{} *", text)).style(Style::new().yellow());
+            }
+            if detail.path.contains(b) {
+                return ListItem::new(text).style(Style::new().green());
+            }
+            ListItem::new(text)
+        })
+        .collect();
+    let list = selectable_list(items, " bundles ".to_string(), app.focus == Focus::Bundles);
+    let mut state = ListState::default().with_selected(Some(detail.bundle_idx));
+    f.render_stateful_widget(list, chunks[1], &mut state);
+
+    // upgrade path and deprecation messages
+    let mut lines: Vec<Line> = vec![
+        Line::from(format!("default channel : {}", detail.default_channel)),
+        Line::from(""),
+    ];
+    match &detail.from {
+        Some(from) => {
+            lines.push(Line::from(format!("from {}", from)).bold());
+            if detail.path.len() == 0 {
+                lines.push(Line::from("  no upgrade available"));
+            }
+            for p in detail.path.iter() {
+                lines.push(Line::from(format!("  -> {}", p)).green());
+            }
+        }
+        None => lines.push(Line::from(
+            "select a bundle (enter) to calculate the upgrade path",
+        )),
+    }
+    if detail.deprecations.entries.len() > 0 {
+        lines.push(Line::from(""));
+        lines.push(Line::from("deprecations (*)").yellow());
+        for e in detail.deprecations.entries.iter() {
+            let name = e.reference.name.clone().unwrap_or(detail.name.clone());
+            lines.push(Line::from(format!("  {} : {}", name, e.message.trim())).yellow());
+        }
+    }
+    f.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::bordered().title(" upgrade path ")),
+        chunks[2],
+    );
+}
@@ -1,6 +1,9 @@
 use crate::api::schema::*;
+use crate::config::validate::*;
+use crate::isc::generate::*;
 use mirror_error::MirrorError;
 use mirror_utils::fs_handler;
+use semver::Version;
 
 // read the 'image set config' file
 pub async fn load_config(config_file: String) -> Result<String, MirrorError> {
@@ -9,8 +12,27 @@ pub async fn load_config(config_file: String) -> Result<String, MirrorError> {
     Ok(data.clone())
 }
 
+// validate and parse the config file, all problems found are reported in the error
+pub fn parse_and_validate_config(file: String, data: String) -> Result<FilterConfig, MirrorError> {
+    let issues = validate_filter_config(&data);
+    if issues.len() > 0 {
+        let details: Vec<String> = issues.iter().map(|i| i.to_string(&file)).collect();
+        return Err(MirrorError::new(&format!(
+            "[parse_and_validate_config] invalid config\n{}",
+            details.join("\n")
+        )));
+    }
+    parse_yaml_config(data)
+}
+
 // parse the 'image set config' file
+// accepts both a FilterConfiguration and an ImageSetConfiguration
 pub fn parse_yaml_config(data: String) -> Result<FilterConfig, MirrorError> {
+    // peek at the kind to decide how to parse the file
+    let res_kind = serde_yaml::from_str::<serde_yaml::Value>(&data);
+    if res_kind.is_ok() && res_kind.unwrap()["kind"] == serde_yaml::Value::from(ISC_KIND) {
+        return parse_isc_config(data);
+    }
     // Parse the string of data into serde_json::ImageSetConfig.
     let res = serde_yaml::from_str(&data);
     if res.is_err() {
@@ -22,3 +44,167 @@ pub fn parse_yaml_config(data: String) -> Result<FilterConfig, MirrorError> {
     let root: FilterConfig = res.unwrap();
     Ok(root)
 }
+
+// convert an existing ImageSetConfiguration into a FilterConfig
+// the first channel (and its minVersion) of each package is used as channel and fromVersion,
+// a package listed in several catalogs keeps one entry per catalog (see FilterOperator::catalogs)
+pub fn parse_isc_config(data: String) -> Result<FilterConfig, MirrorError> {
+    let res = serde_yaml::from_str::<ImageSetConfiguration>(&data);
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[parse_isc_config] {}",
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    let isc = res.unwrap();
+    let mut catalogs: Vec<String> = vec![];
+    let mut operators: Vec<FilterOperator> = vec![];
+    for ctlog in isc.mirror.operators.iter() {
+        if !catalogs.contains(&ctlog.catalog) {
+            catalogs.push(ctlog.catalog.clone());
+        }
+        for pkg in ctlog.packages.iter() {
+            // restrict the package to the catalog it was listed in
+            let mut operator = FilterOperator {
+                name: pkg.name.clone(),
+                channel: None,
+                from_version: pkg.min_version.clone(),
+                catalogs: Some(vec![ctlog.catalog.clone()]),
+            };
+            if pkg.channels.len() > 0 {
+                operator.channel = Some(pkg.channels[0].name.clone());
+                if pkg.channels[0].min_version.is_some() {
+                    operator.from_version = pkg.channels[0].min_version.clone();
+                }
+            }
+            operators.push(operator);
+        }
+    }
+    let mut additional_images: Vec<FilterImage> = vec![];
+    for img in isc.mirror.additional_images.iter() {
+        additional_images.push(FilterImage {
+            name: img.name.clone(),
+        });
+    }
+    Ok(FilterConfig {
+        kind: "FilterConfiguration".to_string(),
+        api_version: isc.api_version.clone(),
+        catalogs,
+        operators: if operators.len() > 0 {
+            Some(operators)
+        } else {
+            None
+        },
+        platform: isc.mirror.platform.as_ref().map(to_filter_platform),
+        additional_images: if additional_images.len() > 0 {
+            Some(additional_images)
+        } else {
+            None
+        },
+    })
+}
+
+// the channel prefix (i.e stable-4.15 -> stable) of the first channel, the lowest minVersion
+// and the highest maxVersion of all channels (the channels are derived again from the catalogs)
+fn to_filter_platform(platform: &Platform) -> FilterPlatform {
+    let prefix = platform
+        .channels
+        .first()
+        .map(|c| match c.name.rsplit_once('-') {
+            Some((prefix, _)) => prefix.to_string(),
+            None => c.name.clone(),
+        });
+    let versions = |f: fn(&PlatformChannel) -> Option<String>| {
+        let mut v: Vec<(Version, String)> = platform
+            .channels
+            .iter()
+            .filter_map(f)
+            .filter_map(|s| Version::parse(&s).ok().map(|v| (v, s)))
+            .collect();
+        v.sort();
+        v
+    };
+    FilterPlatform {
+        channel: prefix,
+        min_version: versions(|c| c.min_version.clone())
+            .first()
+            .map(|(_, s)| s.clone()),
+        max_version: versions(|c| c.max_version.clone())
+            .last()
+            .map(|(_, s)| s.clone()),
+        graph: platform.graph,
+        architectures: platform.architectures.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+
+    #[test]
+    fn parse_yaml_config_isc_pass() {
+        let data = "kind: ImageSetConfiguration
+apiVersion: mirror.openshift.io/v1alpha2
+storageConfig:
+  local:
+    path: ./metadata
+mirror:
+  platform:
+    graph: true
+    channels:
+    - name: stable-4.15
+      maxVersion: 4.15.3
+    - name: stable-4.14
+      minVersion: 4.14.10
+  operators:
+  - catalog: registry.redhat.io/redhat/redhat-operator-index:v4.15
+    packages:
+    - name: aws-load-balancer-operator
+      channels:
+      - name: stable-v1
+        minVersion: 0.2.0
+    - name: jaeger-product
+      minVersion: 1.42.0-5
+  - catalog: registry.redhat.io/redhat/redhat-operator-index:v4.14
+    packages:
+    - name: jaeger-product
+      channels:
+      - name: stable
+        minVersion: 1.34.1-5
+  additionalImages:
+  - name: registry.redhat.io/ubi8/ubi:latest
+"
+        .to_string();
+        let fc = parse_yaml_config(data).expect("should parse isc as filter config");
+        assert_eq!(fc.catalogs.len(), 2);
+        let operators = fc.operators.unwrap();
+        // one entry per catalog, each keeping its own channel and minVersion
+        assert_eq!(operators.len(), 3);
+        assert_eq!(operators[0].channel, Some("stable-v1".to_string()));
+        assert_eq!(operators[0].from_version, Some("0.2.0".to_string()));
+        assert_eq!(operators[1].channel, None);
+        assert_eq!(operators[1].from_version, Some("1.42.0-5".to_string()));
+        assert_eq!(operators[2].name, "jaeger-product");
+        assert_eq!(operators[2].channel, Some("stable".to_string()));
+        assert_eq!(operators[2].from_version, Some("1.34.1-5".to_string()));
+        assert_eq!(
+            operators[1].catalogs,
+            Some(vec![
+                "registry.redhat.io/redhat/redhat-operator-index:v4.15".to_string()
+            ])
+        );
+        assert_eq!(
+            operators[2].catalogs,
+            Some(vec![
+                "registry.redhat.io/redhat/redhat-operator-index:v4.14".to_string()
+            ])
+        );
+        assert_eq!(fc.additional_images.unwrap().len(), 1);
+        let platform = fc.platform.unwrap();
+        assert_eq!(platform.channel, Some("stable".to_string()));
+        assert_eq!(platform.min_version, Some("4.14.10".to_string()));
+        assert_eq!(platform.max_version, Some("4.15.3".to_string()));
+        assert_eq!(platform.graph, Some(true));
+    }
+}
@@ -0,0 +1,405 @@
+use crate::api::schema::*;
+use crate::config::read::parse_isc_config;
+use crate::isc::generate::{ImageSetConfiguration, ISC_KIND};
+use semver::Version;
+use serde_yaml::Value;
+
+pub const FILTER_KIND: &str = "FilterConfiguration";
+pub const FILTER_API_GROUP: &str = "mirror.openshift.io/";
+
+// a single problem found in a filter config (line and column start at 1)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    pub fn to_string(&self, file: &str) -> String {
+        format!("{}:{}:{}: {}", file, self.line, self.column, self.message)
+    }
+}
+
+// validate a filter config, every problem found is reported
+pub fn validate_filter_config(data: &str) -> Vec<ValidationIssue> {
+    let mut issues: Vec<ValidationIssue> = vec![];
+    let res = serde_yaml::from_str::<Value>(data);
+    if res.is_err() {
+        issues.push(from_yaml_error(res.err().unwrap()));
+        return issues;
+    }
+    let root = res.unwrap();
+    if !root.is_mapping() {
+        issues.push(issue(1, 1, "expected a mapping at the top level"));
+        return issues;
+    }
+
+    // kind and apiVersion
+    match root["kind"].as_str() {
+        None => issues.push(issue(1, 1, "missing 'kind'")),
+        Some(kind) => {
+            if kind == ISC_KIND {
+                return validate_isc_config(data);
+            }
+            if kind != FILTER_KIND {
+                let (l, c) = locate(data, "kind", Some(kind), 0);
+                issues.push(issue(
+                    l,
+                    c,
+                    &format!(
+                        "unexpected kind '{}' (expected {} or {})",
+                        kind, FILTER_KIND, ISC_KIND
+                    ),
+                ));
+            }
+        }
+    }
+    match root["apiVersion"].as_str() {
+        None => issues.push(issue(1, 1, "missing 'apiVersion'")),
+        Some(api_version) => {
+            if !api_version.starts_with(FILTER_API_GROUP) {
+                let (l, c) = locate(data, "apiVersion", Some(api_version), 0);
+                issues.push(issue(
+                    l,
+                    c,
+                    &format!(
+                        "unexpected apiVersion '{}' (expected {}<version>)",
+                        api_version, FILTER_API_GROUP
+                    ),
+                ));
+            }
+        }
+    }
+
+    // structure (types, missing fields)
+    let res = serde_yaml::from_str::<FilterConfig>(data);
+    if res.is_err() {
+        issues.push(from_yaml_error(res.err().unwrap()));
+        return issues;
+    }
+    let fc = res.unwrap();
+    validate_fields(data, &fc, "fromVersion", &mut issues);
+    issues
+}
+
+// an imagesetconfig is converted (see parse_isc_config) and the converted config
+// gets the same field checks, the package minVersion/maxVersion are also checked
+fn validate_isc_config(data: &str) -> Vec<ValidationIssue> {
+    let mut issues: Vec<ValidationIssue> = vec![];
+    let res = serde_yaml::from_str::<ImageSetConfiguration>(data);
+    if res.is_err() {
+        issues.push(from_yaml_error(res.err().unwrap()));
+        return issues;
+    }
+    let isc = res.unwrap();
+    let res = parse_isc_config(data.to_string());
+    if res.is_err() {
+        issues.push(issue(1, 1, &res.err().unwrap().to_string().to_lowercase()));
+        return issues;
+    }
+    // the converted fromVersion is the package (or first channel) minVersion
+    validate_fields(data, &res.unwrap(), "minVersion", &mut issues);
+    let mut seen: Vec<String> = vec![];
+    for pkg in isc.mirror.operators.iter().flat_map(|c| c.packages.iter()) {
+        let mut max_versions = vec![pkg.max_version.clone()];
+        max_versions.extend(pkg.channels.iter().map(|c| c.max_version.clone()));
+        for max_version in max_versions.into_iter().flatten() {
+            if Version::parse(&max_version).is_err() {
+                let occurrence = seen.iter().filter(|x| *x == &max_version).count();
+                let (l, c) = locate(data, "maxVersion", Some(&max_version), occurrence);
+                issues.push(issue(
+                    l,
+                    c,
+                    &format!(
+                        "package '{}' maxVersion '{}' is not a valid semver (i.e 1.2.3 or 1.2.3-4)",
+                        pkg.name, max_version
+                    ),
+                ));
+            }
+            seen.push(max_version);
+        }
+    }
+    issues
+}
+
+// catalog and package checks, from_key is the source field of fromVersion
+fn validate_fields(
+    data: &str,
+    fc: &FilterConfig,
+    from_key: &str,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    // catalogs
+    if fc.catalogs.len() == 0 {
+        let (l, c) = locate(data, "catalogs", None, 0);
+        issues.push(issue(
+            l,
+            c,
+            "'catalogs' should contain at least one catalog",
+        ));
+    }
+    let mut seen: Vec<String> = vec![];
+    for catalog in fc.catalogs.iter() {
+        if !catalog.split('/').last().unwrap().contains(':') && !catalog.contains('@') {
+            let (l, c) = locate_item(data, catalog, 0);
+            issues.push(issue(
+                l,
+                c,
+                &format!("catalog '{}' should include a tag (i.e :v4.15)", catalog),
+            ));
+        }
+        if seen.contains(catalog) {
+            let (l, c) = locate_item(data, catalog, 1);
+            issues.push(issue(l, c, &format!("duplicate catalog '{}'", catalog)));
+        }
+        seen.push(catalog.clone());
+    }
+
+    // packages, the same name may be listed once per targeted catalog
+    let mut seen: Vec<String> = vec![];
+    let mut seen_channels: Vec<String> = vec![];
+    let mut seen_versions: Vec<String> = vec![];
+    let mut targeted: Vec<(String, String)> = vec![];
+    for op in fc.operators.clone().unwrap_or(vec![]).iter() {
+        let occurrence = seen.iter().filter(|x| *x == &op.name).count();
+        let effective: Vec<(String, String)> = fc
+            .catalogs
+            .iter()
+            .filter(|c| op.targets_catalog(c))
+            .map(|c| (op.name.clone(), c.clone()))
+            .collect();
+        if op.name.trim().len() == 0 {
+            let (l, c) = locate(data, "name", Some(&op.name), occurrence);
+            issues.push(issue(l, c, "package 'name' should not be empty"));
+        }
+        if op.is_pattern() && op.pattern().is_err() {
+            let (l, c) = locate(data, "name", Some(&op.name), occurrence);
+            issues.push(issue(
+                l,
+                c,
+                &format!(
+                    "package pattern '{}' is invalid {}",
+                    op.name,
+                    op.pattern().err().unwrap().to_string().to_lowercase()
+                ),
+            ));
+        }
+        let duplicate = effective.iter().find(|e| targeted.contains(e));
+        if duplicate.is_some() {
+            let (l, c) = locate(data, "name", Some(&op.name), occurrence);
+            issues.push(issue(
+                l,
+                c,
+                &format!(
+                    "duplicate package '{}' for catalog '{}'",
+                    op.name,
+                    duplicate.unwrap().1
+                ),
+            ));
+        }
+        seen.push(op.name.clone());
+        targeted.extend(effective);
+        if op.channel.is_some() {
+            let channel = op.channel.clone().unwrap();
+            if channel.trim().len() == 0 {
+                let occurrence = seen_channels.iter().filter(|x| *x == &channel).count();
+                let (l, c) = locate(data, "channel", Some(&channel), occurrence);
+                issues.push(issue(
+                    l,
+                    c,
+                    &format!("package '{}' has an empty channel", op.name),
+                ));
+            }
+            seen_channels.push(channel);
+        }
+        for target in op.catalogs.clone().unwrap_or(vec![]).iter() {
+            let single = FilterOperator {
+                catalogs: Some(vec![target.clone()]),
+                ..op.clone()
+            };
+            if !fc.catalogs.iter().any(|c| single.targets_catalog(c)) {
+                let (l, c) = locate_item(data, target, 0);
+                issues.push(issue(
+                    l,
+                    c,
+                    &format!(
+                        "package '{}' targets catalog '{}' which is not in 'catalogs'",
+                        op.name, target
+                    ),
+                ));
+            }
+        }
+        if op.from_version.is_some() {
+            let from_version = op.from_version.clone().unwrap();
+            if Version::parse(&from_version).is_err() {
+                let occurrence = seen_versions.iter().filter(|x| *x == &from_version).count();
+                let (l, c) = locate(data, from_key, Some(&from_version), occurrence);
+                issues.push(issue(
+                    l,
+                    c,
+                    &format!(
+                        "package '{}' {} '{}' is not a valid semver (i.e 1.2.3 or 1.2.3-4)",
+                        op.name, from_key, from_version
+                    ),
+                ));
+            }
+            seen_versions.push(from_version);
+        }
+    }
+}
+
+fn issue(line: usize, column: usize, message: &str) -> ValidationIssue {
+    ValidationIssue {
+        line,
+        column,
+        message: message.to_string(),
+    }
+}
+
+fn from_yaml_error(err: serde_yaml::Error) -> ValidationIssue {
+    let (line, column) = match err.location() {
+        Some(loc) => (loc.line(), loc.column()),
+        None => (1, 1),
+    };
+    issue(line, column, &err.to_string().to_lowercase())
+}
+
+// find the position of the nth 'key: value' (or 'key:' when value is none) in the source
+fn locate(data: &str, key: &str, value: Option<&str>, nth: usize) -> (usize, usize) {
+    let mut count = 0;
+    for (i, line) in data.lines().enumerate() {
+        let trimmed = line.trim_start().trim_start_matches("- ");
+        let res = trimmed.strip_prefix(&format!("{}:", key));
+        if res.is_none() {
+            continue;
+        }
+        let found = res.unwrap().trim().trim_matches(|c| c == '"' || c == '\'');
+        let found = found.split(" #").nth(0).unwrap().trim();
+        if value.is_none() || value.unwrap() == found {
+            if count == nth {
+                return (i + 1, line.find(key).unwrap() + 1);
+            }
+            count += 1;
+        }
+    }
+    (1, 1)
+}
+
+// find the position of the nth list item '- value' in the source
+fn locate_item(data: &str, value: &str, nth: usize) -> (usize, usize) {
+    let mut count = 0;
+    for (i, line) in data.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("- ")
+            && trimmed[2..].trim().trim_matches(|c| c == '"' || c == '\'') == value
+        {
+            if count == nth {
+                return (i + 1, line.find(value).unwrap_or(0) + 1);
+            }
+            count += 1;
+        }
+    }
+    (1, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+
+    #[test]
+    fn validate_filter_config_pass() {
+        let valid = "kind: FilterConfiguration
+apiVersion: mirror.openshift.io/v1alpha2
+catalogs:
+  - registry.redhat.io/redhat/redhat-operator-index:v4.15
+packages:
+  - name: jaeger-product
+    fromVersion: 1.42.0-5
+    catalogs:
+      - redhat-operator-index
+";
+        assert_eq!(validate_filter_config(valid).len(), 0);
+
+        // the same package for different catalogs is not a duplicate
+        let per_catalog = "kind: FilterConfiguration
+apiVersion: mirror.openshift.io/v1alpha2
+catalogs:
+  - registry.redhat.io/redhat/redhat-operator-index:v4.14
+  - registry.redhat.io/redhat/redhat-operator-index:v4.15
+packages:
+  - name: jaeger-product
+    catalogs:
+      - registry.redhat.io/redhat/redhat-operator-index:v4.14
+  - name: jaeger-product
+    catalogs:
+      - registry.redhat.io/redhat/redhat-operator-index:v4.15
+  - name: jaeger-product
+";
+        let issues = validate_filter_config(per_catalog);
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].line, issues[0].column), (13, 5));
+
+        let invalid = "kind: Filter
+apiVersion: v1
+catalogs:
+  - registry.redhat.io/redhat/redhat-operator-index:v4.15
+  - registry.redhat.io/redhat/redhat-operator-index:v4.15
+packages:
+  - name: jaeger-product
+    fromVersion: latest
+  - name: jaeger-product
+    catalogs:
+      - certified-operator-index
+";
+        let issues = validate_filter_config(invalid);
+        let positions: Vec<(usize, usize)> = issues.iter().map(|i| (i.line, i.column)).collect();
+        assert_eq!(issues.len(), 5);
+        assert_eq!(positions, vec![(1, 1), (2, 1), (5, 5), (8, 5), (11, 9)]);
+
+        // repeated invalid values point at each occurrence
+        let repeated = "kind: FilterConfiguration
+apiVersion: mirror.openshift.io/v1alpha2
+catalogs:
+  - registry.redhat.io/redhat/redhat-operator-index:v4.15
+packages:
+  - name: jaeger-product
+    channel: \"\"
+    fromVersion: latest
+  - name: kiali-ossm
+    channel: \"\"
+    fromVersion: latest
+";
+        let issues = validate_filter_config(repeated);
+        let positions: Vec<(usize, usize)> = issues.iter().map(|i| (i.line, i.column)).collect();
+        assert_eq!(positions, vec![(7, 5), (8, 5), (10, 5), (11, 5)]);
+
+        // an imagesetconfig gets the same field checks
+        let isc = "kind: ImageSetConfiguration
+apiVersion: mirror.openshift.io/v2alpha1
+mirror:
+  operators:
+  - catalog: registry.redhat.io/redhat/redhat-operator-index:v4.15
+    packages:
+    - name: jaeger-product
+      minVersion: 1.42
+    - name: aws-load-balancer-operator
+      channels:
+      - name: stable-v1
+        minVersion: 0.2.0
+        maxVersion: latest
+";
+        let issues = validate_filter_config(isc);
+        let positions: Vec<(usize, usize)> = issues.iter().map(|i| (i.line, i.column)).collect();
+        assert_eq!(issues.len(), 2);
+        assert_eq!(positions, vec![(8, 7), (13, 9)]);
+
+        // type errors carry the serde location
+        let issues = validate_filter_config(
+            "kind: FilterConfiguration\napiVersion: mirror.openshift.io/v1alpha2\ncatalogs: test\n",
+        );
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, 3);
+    }
+}
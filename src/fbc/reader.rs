@@ -0,0 +1,558 @@
+use base64::{engine::general_purpose, Engine as _};
+use custom_logger::*;
+use mirror_error::MirrorError;
+use serde::Deserialize;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use walkdir::WalkDir;
+
+// written next to the catalog cache by update (the full image reference of the catalog)
+pub const CATALOG_REFERENCE_FILE: &str = "catalog-reference";
+
+// olm.bundle schema (only the fields we are interested in)
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OlmBundle {
+    #[serde(rename = "schema")]
+    pub schema: String,
+
+    #[serde(rename = "name")]
+    pub name: String,
+
+    #[serde(rename = "package")]
+    pub package: String,
+
+    #[serde(rename = "image")]
+    pub image: Option<String>,
+
+    #[serde(rename = "properties")]
+    pub properties: Option<Vec<Property>>,
+
+    #[serde(rename = "relatedImages")]
+    pub related_images: Option<Vec<RelatedImage>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Property {
+    #[serde(rename = "type")]
+    pub property_type: String,
+
+    #[serde(rename = "value")]
+    pub value: Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RelatedImage {
+    #[serde(rename = "name")]
+    pub name: Option<String>,
+
+    #[serde(rename = "image")]
+    pub image: String,
+}
+
+// olm.package schema
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OlmPackage {
+    #[serde(rename = "schema")]
+    pub schema: String,
+
+    #[serde(rename = "name")]
+    pub name: String,
+
+    #[serde(rename = "defaultChannel")]
+    pub default_channel: Option<String>,
+
+    #[serde(rename = "description")]
+    pub description: Option<String>,
+}
+
+// olm.channel schema
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OlmChannel {
+    #[serde(rename = "schema")]
+    pub schema: String,
+
+    #[serde(rename = "name")]
+    pub name: String,
+
+    #[serde(rename = "package")]
+    pub package: String,
+
+    #[serde(rename = "entries", default)]
+    pub entries: Vec<OlmChannelEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OlmChannelEntry {
+    #[serde(rename = "name")]
+    pub name: String,
+
+    #[serde(rename = "replaces")]
+    pub replaces: Option<String>,
+
+    #[serde(rename = "skips")]
+    pub skips: Option<Vec<String>>,
+
+    #[serde(rename = "skipRange")]
+    pub skip_range: Option<String>,
+}
+
+// olm.deprecations schema (package, channels and bundles marked as deprecated)
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OlmDeprecations {
+    #[serde(rename = "schema")]
+    pub schema: String,
+
+    #[serde(rename = "package")]
+    pub package: String,
+
+    #[serde(rename = "entries", default)]
+    pub entries: Vec<DeprecationEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeprecationEntry {
+    #[serde(rename = "reference")]
+    pub reference: DeprecationReference,
+
+    #[serde(rename = "message", default)]
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeprecationReference {
+    #[serde(rename = "schema")]
+    pub schema: String,
+
+    #[serde(rename = "name")]
+    pub name: Option<String>,
+}
+
+impl OlmBundle {
+    // all property values of a given type (i.e olm.package.required)
+    pub fn get_properties(&self, property_type: &str) -> Vec<Value> {
+        let mut values = vec![];
+        for p in self.properties.clone().unwrap_or(vec![]).iter() {
+            if p.property_type == property_type {
+                values.push(p.value.clone());
+            }
+        }
+        values
+    }
+
+    // the bundle version from the olm.package property
+    pub fn version(&self) -> Option<String> {
+        let values = self.get_properties("olm.package");
+        if values.len() == 0 || !values[0]["version"].is_string() {
+            return None;
+        }
+        Some(values[0]["version"].as_str().unwrap().to_string())
+    }
+
+    // the csv metadata (displayName, description, keywords, provider ...)
+    // older catalogs don't have the olm.csv.metadata property, the same fields
+    // are then taken from the csv in the olm.bundle.object properties
+    pub fn csv_metadata(&self) -> Option<Value> {
+        let metadata = self.get_properties("olm.csv.metadata").first().cloned();
+        if metadata.is_some() {
+            return metadata;
+        }
+        let csv = self.csv()?;
+        let mut metadata = csv["spec"].clone();
+        if !metadata.is_object() {
+            return None;
+        }
+        let fields = metadata.as_object_mut().unwrap();
+        for key in ["annotations", "labels"] {
+            if csv["metadata"][key].is_object() {
+                fields.insert(key.to_string(), csv["metadata"][key].clone());
+            }
+        }
+        Some(metadata)
+    }
+
+    // the (base64 decoded) manifests of the olm.bundle.object properties
+    pub fn objects(&self) -> Vec<String> {
+        self.get_properties("olm.bundle.object")
+            .iter()
+            .filter_map(|v| v["data"].as_str().map(|d| d.to_string()))
+            .filter_map(|d| general_purpose::STANDARD.decode(d).ok())
+            .filter_map(|d| String::from_utf8(d).ok())
+            .collect()
+    }
+
+    // the ClusterServiceVersion of the olm.bundle.object properties
+    pub fn csv(&self) -> Option<Value> {
+        self.objects()
+            .iter()
+            .filter_map(|o| serde_json::from_str::<Value>(o).ok())
+            .find(|v| v["kind"] == "ClusterServiceVersion")
+    }
+}
+
+impl OlmChannel {
+    // the channel head, the entry not replaced or skipped by any other entry
+    pub fn head(&self) -> Option<String> {
+        for e in self.entries.iter() {
+            let replaced = self.entries.iter().any(|x| {
+                x.replaces.as_ref() == Some(&e.name)
+                    || x.skips.clone().unwrap_or(vec![]).contains(&e.name)
+            });
+            if !replaced {
+                return Some(e.name.clone());
+            }
+        }
+        None
+    }
+}
+
+impl OlmDeprecations {
+    // the deprecation message for a schema (olm.package, olm.channel, olm.bundle) and name
+    // the package reference has no name
+    pub fn message(&self, schema: &str, name: Option<&str>) -> Option<String> {
+        self.entries
+            .iter()
+            .find(|e| {
+                e.reference.schema == schema
+                    && (schema == "olm.package" || e.reference.name.as_deref() == name)
+            })
+            .map(|e| e.message.clone())
+    }
+
+    pub fn package(&self) -> Option<String> {
+        self.message("olm.package", None)
+    }
+
+    pub fn channel(&self, name: &str) -> Option<String> {
+        self.message("olm.channel", Some(name))
+    }
+
+    pub fn bundle(&self, name: &str) -> Option<String> {
+        self.message("olm.bundle", Some(name))
+    }
+}
+
+// the untarred cache directory for a catalog reference
+// i.e registry.redhat.io/redhat/redhat-operator-index:v4.15 -> <dir>/redhat-operator-index/v4.15/amd64/cache/
+pub fn get_cache_dir(dir: String, catalog: String) -> String {
+    let last = catalog.split('/').last().unwrap();
+    let index_dir = last.replace(":", "/");
+    format!("{}/{}/{}", dir, &index_dir, "/amd64/cache/")
+}
+
+// find the 'configs' directory of a cached catalog
+pub async fn get_configs_dir(
+    log: &Logging,
+    dir: String,
+    catalog: String,
+) -> Result<String, MirrorError> {
+    let cache_dir = get_cache_dir(dir, catalog.clone());
+    log.trace(&format!("[get_configs_dir] looking in {}", cache_dir));
+    match find_configs_dir(&cache_dir) {
+        Some(config_dir) => Ok(config_dir),
+        None => Err(MirrorError::new(&format!(
+            "[get_configs_dir] no 'configs' directory found for catalog {} (has it been updated ?)",
+            catalog
+        ))),
+    }
+}
+
+// the full catalog reference is recorded next to the cache by update
+// i.e <dir>/redhat-operator-index/v4.15/amd64/catalog-reference
+pub fn save_catalog_reference(dir: String, catalog: String) -> Result<(), MirrorError> {
+    let last = catalog.split('/').last().unwrap();
+    let file = format!(
+        "{}/{}/amd64/{}",
+        dir,
+        last.replace(":", "/"),
+        CATALOG_REFERENCE_FILE
+    );
+    let res = fs::write(&file, &catalog);
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[save_catalog_reference] writing {} {}",
+            file,
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    Ok(())
+}
+
+// the full catalog reference of a cache directory (<...>/amd64/cache), none for caches
+// updated before the reference was recorded
+pub fn cached_catalog_reference(cache_dir: &str) -> Option<String> {
+    let amd64_dir = cache_dir.trim_end_matches('/').trim_end_matches("/cache");
+    fs::read_to_string(format!("{}/{}", amd64_dir, CATALOG_REFERENCE_FILE))
+        .ok()
+        .map(|r| r.trim().to_string())
+        .filter(|r| r.len() > 0)
+}
+
+// read every declarative config blob of a package
+// handles both json (stream of objects) and yaml (multi document) files
+pub fn read_package_blobs(config_dir: String, package: String) -> Result<Vec<Value>, MirrorError> {
+    let package_dir = format!("{}/{}", config_dir, package);
+    let mut blobs: Vec<Value> = vec![];
+    for entry in WalkDir::new(&package_dir).into_iter() {
+        if entry.is_err() {
+            return Err(MirrorError::new(&format!(
+                "[read_package_blobs] reading {} {}",
+                package_dir,
+                entry.err().unwrap().to_string().to_lowercase()
+            )));
+        }
+        let e = entry.unwrap();
+        let path = e.path().display().to_string();
+        // skip the files generated by build_updated_configs
+        if !e.file_type().is_file() || path.contains("/updated-configs") {
+            continue;
+        }
+        let res = fs::read_to_string(&path);
+        if res.is_err() {
+            return Err(MirrorError::new(&format!(
+                "[read_package_blobs] reading {} {}",
+                path,
+                res.err().unwrap().to_string().to_lowercase()
+            )));
+        }
+        let data = res.unwrap();
+        if path.ends_with(".json") {
+            for v in serde_json::Deserializer::from_str(&data).into_iter::<Value>() {
+                if v.is_err() {
+                    return Err(MirrorError::new(&format!(
+                        "[read_package_blobs] parsing {} {}",
+                        path,
+                        v.err().unwrap().to_string().to_lowercase()
+                    )));
+                }
+                blobs.push(v.unwrap());
+            }
+        } else if path.ends_with(".yaml") || path.ends_with(".yml") {
+            for doc in serde_yaml::Deserializer::from_str(&data) {
+                let v = Value::deserialize(doc);
+                if v.is_err() {
+                    return Err(MirrorError::new(&format!(
+                        "[read_package_blobs] parsing {} {}",
+                        path,
+                        v.err().unwrap().to_string().to_lowercase()
+                    )));
+                }
+                let value = v.unwrap();
+                if !value.is_null() {
+                    blobs.push(value);
+                }
+            }
+        }
+    }
+    Ok(blobs)
+}
+
+// the 'configs' directory of a cached catalog (i.e <cache>/<layer>/configs)
+pub fn find_configs_dir(cache_dir: &String) -> Option<String> {
+    WalkDir::new(cache_dir)
+        .max_depth(4)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .find(|e| e.file_type().is_dir() && e.file_name() == "configs")
+        .map(|e| e.path().display().to_string())
+}
+
+// list the cached catalogs in the working directory as (name:version, cache directory)
+// i.e <dir>/redhat-operator-index/v4.15/amd64/cache -> redhat-operator-index:v4.15
+pub fn list_cached_catalogs(dir: String) -> Vec<(String, String)> {
+    let mut catalogs = vec![];
+    for entry in WalkDir::new(&dir).min_depth(4).max_depth(4).into_iter() {
+        if entry.is_err() {
+            continue;
+        }
+        let e = entry.unwrap();
+        let path = e.path().display().to_string();
+        if !e.file_type().is_dir() || !path.ends_with("/amd64/cache") {
+            continue;
+        }
+        let parts: Vec<&str> = path
+            .trim_end_matches("/amd64/cache")
+            .rsplitn(3, '/')
+            .collect();
+        if parts.len() == 3 {
+            catalogs.push((format!("{}:{}", parts[1], parts[0]), path.clone()));
+        }
+    }
+    catalogs.sort();
+    catalogs
+}
+
+// check if a file of the package contains all the strings, a cheap filter
+// used to avoid parsing every package of a catalog
+pub fn package_contains(config_dir: String, package: String, needles: &[&str]) -> bool {
+    let package_dir = format!("{}/{}", config_dir, package);
+    for entry in WalkDir::new(&package_dir).into_iter().flatten() {
+        let path = entry.path().display().to_string();
+        if !entry.file_type().is_file() || path.contains("/updated-configs") {
+            continue;
+        }
+        let data = fs::read_to_string(&path).unwrap_or_default();
+        if needles.iter().all(|n| data.contains(n)) {
+            return true;
+        }
+    }
+    false
+}
+
+// the olm.deprecations of every package of a catalog 'configs' directory, only the
+// packages with a file mentioning olm.deprecations are parsed
+pub fn catalog_deprecations(config_dir: String) -> HashMap<String, OlmDeprecations> {
+    let mut deprecations = HashMap::new();
+    for name in list_packages(config_dir.clone()).iter() {
+        if !package_contains(config_dir.clone(), name.clone(), &["olm.deprecations"]) {
+            continue;
+        }
+        let blobs = read_package_blobs(config_dir.clone(), name.clone()).unwrap_or(vec![]);
+        deprecations.insert(name.clone(), get_deprecations(&blobs));
+    }
+    deprecations
+}
+
+// list the package names of a catalog 'configs' directory
+pub fn list_packages(config_dir: String) -> Vec<String> {
+    let mut packages = vec![];
+    for entry in WalkDir::new(&config_dir)
+        .min_depth(1)
+        .max_depth(1)
+        .into_iter()
+    {
+        if entry.is_ok() && entry.as_ref().unwrap().file_type().is_dir() {
+            packages.push(entry.unwrap().file_name().to_string_lossy().to_string());
+        }
+    }
+    packages.sort();
+    packages
+}
+
+// filter the blobs on the olm.package schema
+pub fn get_package(blobs: &Vec<Value>) -> Option<OlmPackage> {
+    for b in blobs.iter() {
+        if b["schema"] == "olm.package" {
+            let res = serde_json::from_value::<OlmPackage>(b.clone());
+            if res.is_ok() {
+                return Some(res.unwrap());
+            }
+        }
+    }
+    None
+}
+
+// filter the blobs on the olm.channel schema
+pub fn get_channels(blobs: &Vec<Value>) -> Vec<OlmChannel> {
+    let mut channels = vec![];
+    for b in blobs.iter() {
+        if b["schema"] == "olm.channel" {
+            let res = serde_json::from_value::<OlmChannel>(b.clone());
+            if res.is_ok() {
+                channels.push(res.unwrap());
+            }
+        }
+    }
+    channels
+}
+
+// filter the blobs on the olm.bundle schema
+// bundles that fail to parse are skipped, use parse_bundles to report them
+pub fn get_bundles(blobs: &[Value]) -> Vec<OlmBundle> {
+    parse_bundles(blobs).0
+}
+
+// the parsed olm.bundle blobs and an error (name: reason) for each one that failed to parse
+pub fn parse_bundles(blobs: &[Value]) -> (Vec<OlmBundle>, Vec<String>) {
+    let mut bundles = vec![];
+    let mut errors = vec![];
+    for b in blobs.iter() {
+        if b["schema"] == "olm.bundle" {
+            let res = serde_json::from_value::<OlmBundle>(b.clone());
+            if res.is_ok() {
+                bundles.push(res.unwrap());
+            } else {
+                errors.push(format!(
+                    "{}: {}",
+                    b["name"].as_str().unwrap_or("<unnamed>"),
+                    res.err().unwrap().to_string().to_lowercase()
+                ));
+            }
+        }
+    }
+    (bundles, errors)
+}
+
+// filter the blobs on the olm.deprecations schema (at most one per package)
+pub fn get_deprecations(blobs: &Vec<Value>) -> OlmDeprecations {
+    for b in blobs.iter() {
+        if b["schema"] == "olm.deprecations" {
+            let res = serde_json::from_value::<OlmDeprecations>(b.clone());
+            if res.is_ok() {
+                return res.unwrap();
+            }
+        }
+    }
+    OlmDeprecations::default()
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+
+    #[test]
+    fn read_package_blobs_pass() {
+        let config_dir = "./test-artifacts/fbc/configs".to_string();
+        fs::create_dir_all(format!("{}/test-operator/updated-configs", config_dir))
+            .expect("should create fbc test folder");
+        fs::write(
+            format!("{}/test-operator/catalog.json", config_dir),
+            "{ \"schema\": \"olm.package\", \"name\": \"test-operator\" }
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.1\", \"package\": \"test-operator\",
+              \"image\": \"quay.io/test/bundle@sha256:01\",
+              \"relatedImages\": [ { \"name\": \"operator\", \"image\": \"quay.io/test/operator@sha256:02\" } ] }",
+        )
+        .expect("should write json catalog");
+        fs::write(
+            format!("{}/test-operator/bundles.yaml", config_dir),
+            "---\nschema: olm.bundle\nname: test-operator.v0.0.2\npackage: test-operator\n---\nschema: olm.channel\nname: stable\npackage: test-operator\n---\nschema: olm.deprecations\npackage: test-operator\nentries:\n- reference:\n    schema: olm.channel\n    name: alpha\n  message: alpha is deprecated\n- reference:\n    schema: olm.bundle\n    name: test-operator.v0.0.1\n  message: use v0.0.2\n",
+        )
+        .expect("should write yaml catalog");
+        fs::write(
+            format!(
+                "{}/test-operator/updated-configs/olm.bundle.json",
+                config_dir
+            ),
+            "{ \"schema\": \"olm.bundle\", \"name\": \"ignored\", \"package\": \"test-operator\" }",
+        )
+        .expect("should write updated-configs file");
+
+        let blobs = read_package_blobs(config_dir.clone(), "test-operator".to_string())
+            .expect("should read package blobs");
+        assert_eq!(blobs.len(), 5);
+        let mut bundles = get_bundles(&blobs);
+        bundles.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(bundles.len(), 2);
+        assert_eq!(bundles[0].name, "test-operator.v0.0.1");
+        assert_eq!(bundles[0].related_images.as_ref().unwrap().len(), 1);
+        assert_eq!(bundles[1].image, None);
+        assert_eq!(get_package(&blobs).unwrap().name, "test-operator");
+        assert_eq!(get_channels(&blobs).len(), 1);
+        assert_eq!(list_packages(config_dir.clone()), vec!["test-operator"]);
+        let deprecations = get_deprecations(&blobs);
+        assert_eq!(deprecations.package(), None);
+        assert_eq!(
+            deprecations.channel("alpha"),
+            Some("alpha is deprecated".to_string())
+        );
+        assert_eq!(deprecations.channel("stable"), None);
+        assert_eq!(
+            deprecations.bundle("test-operator.v0.0.1"),
+            Some("use v0.0.2".to_string())
+        );
+
+        fs::remove_dir_all("./test-artifacts/fbc").expect("should delete fbc test folder");
+    }
+}
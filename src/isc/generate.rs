@@ -1,26 +1,112 @@
+use mirror_error::MirrorError;
 use serde_derive::{Deserialize, Serialize};
 
+pub const ISC_KIND: &str = "ImageSetConfiguration";
+pub const ISC_API_GROUP: &str = "mirror.openshift.io";
+
+// supported ImageSetConfiguration api versions
+// v1alpha2 (oc-mirror v1) and v2alpha1 (oc-mirror v2) share the fields generated here,
+// except the package bundles selection which only v2alpha1 supports
+pub const ISC_API_VERSIONS: [&str; 2] = ["v1alpha2", "v2alpha1"];
+
+// how packages are expressed in the generated isc
+// bundles : the individual bundles of the upgrade path
+// channels : the channel with minVersion/maxVersion of the upgrade path
+// versions : minVersion/maxVersion at package level
+pub const ISC_STYLES: [&str; 3] = ["bundles", "channels", "versions"];
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct IscConfig {
     pub api_version: String,
+    pub platform: Option<Platform>,
     pub operators: Vec<Catalog>,
+    pub additional_images: Vec<Image>,
     pub name: String,
     pub version: String,
 }
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+/// ImageSetConfiguration schema (the subset used by this tool)
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageSetConfiguration {
+    pub kind: String,
+    pub api_version: String,
+    pub mirror: Mirror,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Mirror {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<Platform>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub operators: Vec<Catalog>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub additional_images: Vec<Image>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Platform {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub architectures: Option<Vec<String>>,
+    #[serde(default)]
+    pub channels: Vec<PlatformChannel>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub graph: Option<bool>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlatformChannel {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_version: Option<String>,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub channel_type: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Image {
+    pub name: String,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Catalog {
     pub catalog: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub packages: Vec<Package>,
 }
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub struct Package {
     pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_channel: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub channels: Vec<IscChannel>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub bundles: Vec<Bundle>,
 }
 
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IscChannel {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_version: Option<String>,
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Bundle {
     pub name: String,
@@ -30,39 +116,159 @@ impl IscConfig {
     pub fn new(api_v: String) -> Self {
         IscConfig {
             api_version: api_v.clone(),
+            platform: None,
             operators: vec![],
+            additional_images: vec![],
             name: env!("CARGO_PKG_NAME").to_string(),
             version: env!("CARGO_PKG_VERSION").to_string(),
         }
     }
 
-    pub fn to_yaml(&self) -> String {
-        let mut body = "".to_string();
-        let yaml = format!(
-            "\n---\napiVersion: mirror.openshift.io/{}
-kind: ImageSetConfiguration
-#metadata:
-#  name: ImageSetConfiguration
-#  annotations: 
-#    autogenerated: '{} {}' 
-mirror:
-  operators:
-",
-            self.api_version, self.name, self.version
-        );
-        for ops in self.operators.iter() {
-            body += &format!("  - catalog: {}\n", ops.catalog);
-            body += &format!("    packages:");
-            for pkg in ops.packages.iter() {
-                body += &format!("\n    - name: {}", pkg.name);
-                body += &format!("\n      bundles:");
-                pkg.bundles.iter().for_each(|b| {
-                    body += &format!("\n      - name: {}", b.name);
-                });
+    // build the typed ImageSetConfiguration
+    // catalogs without packages are left out, as an empty package list
+    // would mirror the complete catalog
+    pub fn to_isc(&self) -> ImageSetConfiguration {
+        ImageSetConfiguration {
+            kind: ISC_KIND.to_string(),
+            api_version: format!("{}/{}", ISC_API_GROUP, self.api_version),
+            mirror: Mirror {
+                platform: self.platform.clone(),
+                additional_images: self.additional_images.clone(),
+                operators: self
+                    .operators
+                    .iter()
+                    .filter(|c| c.packages.len() > 0)
+                    .cloned()
+                    .collect(),
+            },
+        }
+    }
+
+    // check the generated content is valid for the api version
+    pub fn validate(&self) -> Result<(), MirrorError> {
+        if self.api_version != ISC_API_VERSIONS[0] {
+            return Ok(());
+        }
+        for catalog in self.operators.iter() {
+            if let Some(pkg) = catalog.packages.iter().find(|p| p.bundles.len() > 0) {
+                return Err(MirrorError::new(&format!(
+                    "[IscConfig] package '{}' selects bundles, which {}/{} doesn't support (use --isc-style channels|versions or --api-version {})",
+                    pkg.name, ISC_API_GROUP, ISC_API_VERSIONS[0], ISC_API_VERSIONS[1]
+                )));
             }
-            body += &format!("\n");
         }
-        let all = yaml + &body;
-        all
+        Ok(())
+    }
+
+    pub fn to_yaml(&self) -> String {
+        let yaml = serde_yaml::to_string(&self.to_isc()).unwrap();
+        format!(
+            "---\n# autogenerated by {} {}\n{}",
+            self.name, self.version, yaml
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+
+    // an isc with every generated section, packages selected by bundles only when requested
+    fn sample_isc(api_version: &str, bundles: bool) -> IscConfig {
+        let mut isc = IscConfig::new(api_version.to_string());
+        let mut packages = vec![
+            Package {
+                name: "kubernetes-nmstate-operator".to_string(),
+                default_channel: Some("4.14".to_string()),
+                channels: vec![IscChannel {
+                    name: "4.14".to_string(),
+                    min_version: Some("4.14.0-202401151553".to_string()),
+                    max_version: Some("4.14.0-202402132033".to_string()),
+                }],
+                ..Default::default()
+            },
+            Package {
+                name: "jaeger-product".to_string(),
+                min_version: Some("1.42.0-5".to_string()),
+                max_version: Some("1.51.0-1".to_string()),
+                ..Default::default()
+            },
+        ];
+        if bundles {
+            packages.push(Package {
+                name: "amq-streams".to_string(),
+                bundles: vec![Bundle {
+                    name: "amqstreams.v2.6.0-2".to_string(),
+                }],
+                ..Default::default()
+            });
+        }
+        isc.operators.push(Catalog {
+            catalog: "registry.redhat.io/redhat/redhat-operator-index:v4.15".to_string(),
+            packages,
+        });
+        isc.platform = Some(Platform {
+            architectures: None,
+            channels: vec![PlatformChannel {
+                name: "stable-4.15".to_string(),
+                min_version: Some("4.15.3".to_string()),
+                max_version: None,
+                channel_type: Some("ocp".to_string()),
+            }],
+            graph: Some(true),
+        });
+        isc.additional_images.push(Image {
+            name: "registry.redhat.io/ubi8/ubi:latest".to_string(),
+        });
+        // catalog without packages should not be rendered
+        isc.operators.push(Catalog {
+            catalog: "registry.redhat.io/redhat/redhat-operator-index:v4.14".to_string(),
+            packages: vec![],
+        });
+        isc
+    }
+
+    // the yaml parses back to the same imagesetconfig
+    fn round_trip(isc: &IscConfig) -> (String, ImageSetConfiguration) {
+        let yaml = isc.to_yaml();
+        let parsed: ImageSetConfiguration =
+            serde_yaml::from_str(&yaml).expect("should parse generated isc");
+        assert_eq!(parsed, isc.to_isc());
+        assert_eq!(parsed.kind, "ImageSetConfiguration");
+        assert_eq!(
+            parsed.api_version,
+            format!("mirror.openshift.io/{}", isc.api_version)
+        );
+        assert_eq!(parsed.mirror.operators.len(), 1);
+        assert_eq!(yaml.contains("minVersion: 1.42.0-5"), true);
+        assert_eq!(yaml.contains("defaultChannel: '4.14'"), true);
+        assert_eq!(parsed.mirror.platform.as_ref().unwrap().channels.len(), 1);
+        assert_eq!(parsed.mirror.additional_images.len(), 1);
+        (yaml, parsed)
+    }
+
+    #[test]
+    fn to_yaml_v1alpha2_pass() {
+        let isc = sample_isc("v1alpha2", false);
+        isc.validate().expect("should be a valid v1alpha2 isc");
+        let (yaml, parsed) = round_trip(&isc);
+        assert_eq!(parsed.mirror.operators[0].packages.len(), 2);
+        assert_eq!(yaml.contains("bundles:"), false);
+
+        // oc-mirror v1 can't select bundles
+        let err = sample_isc("v1alpha2", true).validate().err().unwrap();
+        assert!(err
+            .to_string()
+            .contains("package 'amq-streams' selects bundles"));
+    }
+
+    #[test]
+    fn to_yaml_v2alpha1_pass() {
+        let isc = sample_isc("v2alpha1", true);
+        isc.validate().expect("should be a valid v2alpha1 isc");
+        let (yaml, parsed) = round_trip(&isc);
+        assert_eq!(parsed.mirror.operators[0].packages.len(), 3);
+        assert_eq!(yaml.contains("- name: amqstreams.v2.6.0-2"), true);
     }
 }
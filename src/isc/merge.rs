@@ -0,0 +1,257 @@
+use crate::isc::generate::*;
+use mirror_error::MirrorError;
+use serde_yaml::{Mapping, Value};
+
+// package fields selecting the content to mirror, oc-mirror rejects bundles
+// combined with channels or versions
+const SELECTION_KEYS: [&str; 4] = ["bundles", "channels", "minVersion", "maxVersion"];
+
+// merge the generated operators into an existing ImageSetConfiguration
+// catalogs and packages are matched by name, the generated selection (bundles, channels,
+// minVersion, maxVersion) replaces the existing one so bundles that left the upgrade path
+// aren't mirrored anymore, the other package fields are kept
+// every other section (and the ordering of the document) is preserved, yaml comments are not
+pub fn merge_isc(existing: String, isc: &IscConfig) -> Result<String, MirrorError> {
+    let res = serde_yaml::from_str::<Value>(&existing);
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[merge_isc] parsing existing imagesetconfig {}",
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    let mut root = res.unwrap();
+    if root["kind"] != Value::from(ISC_KIND) {
+        return Err(MirrorError::new(
+            "[merge_isc] existing file is not an ImageSetConfiguration",
+        ));
+    }
+    let root_map = root.as_mapping_mut().unwrap();
+    let mirror = get_or_insert(root_map, "mirror", Value::Mapping(Mapping::new()));
+    if !mirror.is_mapping() {
+        return Err(MirrorError::new("[merge_isc] 'mirror' should be a mapping"));
+    }
+    let operators = get_or_insert(
+        mirror.as_mapping_mut().unwrap(),
+        "operators",
+        Value::Sequence(vec![]),
+    );
+    if !operators.is_sequence() {
+        return Err(MirrorError::new(
+            "[merge_isc] 'mirror.operators' should be a sequence",
+        ));
+    }
+    let operators = operators.as_sequence_mut().unwrap();
+
+    // only catalogs with packages are generated (see IscConfig::to_isc)
+    for ctlog in isc.to_isc().mirror.operators.iter() {
+        let pos = operators
+            .iter()
+            .position(|o| o["catalog"] == Value::from(ctlog.catalog.clone()));
+        if pos.is_none() {
+            operators.push(to_value(ctlog)?);
+            continue;
+        }
+        let existing_catalog = operators[pos.unwrap()].as_mapping_mut();
+        if existing_catalog.is_none() {
+            return Err(MirrorError::new(&format!(
+                "[merge_isc] entry for catalog {} should be a mapping",
+                ctlog.catalog
+            )));
+        }
+        let packages = get_or_insert(
+            existing_catalog.unwrap(),
+            "packages",
+            Value::Sequence(vec![]),
+        );
+        let packages = packages.as_sequence_mut();
+        if packages.is_none() {
+            return Err(MirrorError::new(&format!(
+                "[merge_isc] 'packages' for catalog {} should be a sequence",
+                ctlog.catalog
+            )));
+        }
+        let packages = packages.unwrap();
+        for pkg in ctlog.packages.iter() {
+            let generated = to_value(pkg)?;
+            let pos = packages
+                .iter()
+                .position(|p| p["name"] == Value::from(pkg.name.clone()));
+            if pos.is_none() {
+                packages.push(generated);
+                continue;
+            }
+            let existing_pkg = packages[pos.unwrap()].as_mapping_mut();
+            if existing_pkg.is_none() {
+                return Err(MirrorError::new(&format!(
+                    "[merge_isc] package {} for catalog {} should be a mapping",
+                    pkg.name, ctlog.catalog
+                )));
+            }
+            merge_package(existing_pkg.unwrap(), generated.as_mapping().unwrap());
+        }
+    }
+
+    let res = serde_yaml::to_string(&root);
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[merge_isc] {}",
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    Ok(format!("---\n{}", res.unwrap()))
+}
+
+// the generated selection replaces the existing one, the generated fields are updated
+fn merge_package(existing: &mut Mapping, generated: &Mapping) {
+    for key in SELECTION_KEYS.iter() {
+        existing.remove(*key);
+    }
+    for (k, v) in generated.iter() {
+        existing.insert(k.clone(), v.clone());
+    }
+}
+
+// comments can't be kept when the merged document is written back
+pub fn has_comments(contents: &str) -> bool {
+    contents
+        .lines()
+        .any(|l| l.trim_start().starts_with('#') || l.contains(" #"))
+}
+
+fn get_or_insert<'a>(map: &'a mut Mapping, key: &str, default: Value) -> &'a mut Value {
+    if !map.contains_key(key) || map.get(key).unwrap().is_null() {
+        map.insert(Value::from(key), default);
+    }
+    map.get_mut(key).unwrap()
+}
+
+fn to_value<T: serde::Serialize>(t: &T) -> Result<Value, MirrorError> {
+    let res = serde_yaml::to_value(t);
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[merge_isc] {}",
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    Ok(res.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+
+    #[test]
+    fn merge_isc_pass() {
+        let existing = "kind: ImageSetConfiguration
+apiVersion: mirror.openshift.io/v2alpha1
+mirror:
+  platform:
+    channels:
+    - name: stable-4.15
+  operators:
+  - catalog: registry.redhat.io/redhat/redhat-operator-index:v4.15
+    packages:
+    - name: amq-streams
+      bundles:
+      - name: amqstreams.v2.4.0-0
+      - name: amqstreams.v2.5.0-0
+    - name: jaeger-product
+      channels:
+      - name: stable
+    - name: kubernetes-nmstate-operator
+      bundles:
+      - name: kubernetes-nmstate-operator.4.15.0-202401010000
+  additionalImages:
+  - name: registry.redhat.io/ubi8/ubi:latest
+"
+        .to_string();
+
+        let mut isc = IscConfig::new("v2alpha1".to_string());
+        isc.operators.push(Catalog {
+            catalog: "registry.redhat.io/redhat/redhat-operator-index:v4.15".to_string(),
+            packages: vec![
+                Package {
+                    name: "amq-streams".to_string(),
+                    bundles: vec![
+                        Bundle {
+                            name: "amqstreams.v2.5.0-0".to_string(),
+                        },
+                        Bundle {
+                            name: "amqstreams.v2.6.0-2".to_string(),
+                        },
+                    ],
+                    ..Default::default()
+                },
+                Package {
+                    name: "kubernetes-nmstate-operator".to_string(),
+                    min_version: Some("4.15.0-202402082307".to_string()),
+                    ..Default::default()
+                },
+                Package {
+                    name: "jaeger-product".to_string(),
+                    bundles: vec![Bundle {
+                        name: "jaeger-operator.v1.51.0-1".to_string(),
+                    }],
+                    ..Default::default()
+                },
+            ],
+        });
+        isc.operators.push(Catalog {
+            catalog: "registry.redhat.io/redhat/certified-operator-index:v4.15".to_string(),
+            packages: vec![Package {
+                name: "gpu-operator-certified".to_string(),
+                ..Default::default()
+            }],
+        });
+
+        let merged = merge_isc(existing, &isc).expect("should merge isc");
+        let parsed: Value = serde_yaml::from_str(&merged).expect("should parse merged isc");
+        let mirror = parsed["mirror"].as_mapping().unwrap();
+        // ordering of the sections is preserved
+        let keys: Vec<&str> = mirror.keys().map(|k| k.as_str().unwrap()).collect();
+        assert_eq!(keys, vec!["platform", "operators", "additionalImages"]);
+        let operators = parsed["mirror"]["operators"].as_sequence().unwrap();
+        assert_eq!(operators.len(), 2);
+        let packages = operators[0]["packages"].as_sequence().unwrap();
+        assert_eq!(packages.len(), 3);
+        // bundles no longer on the upgrade path are dropped
+        assert_eq!(
+            packages[0]["bundles"],
+            serde_yaml::from_str::<Value>(
+                "[ { name: amqstreams.v2.5.0-0 }, { name: amqstreams.v2.6.0-2 } ]"
+            )
+            .unwrap()
+        );
+        // the generated selection replaces the existing one (bundles can't be mixed with channels or versions)
+        assert_eq!(packages[1]["name"], Value::from("jaeger-product"));
+        assert_eq!(packages[1].get("channels"), None);
+        assert_eq!(packages[1]["bundles"].as_sequence().unwrap().len(), 1);
+        assert_eq!(
+            packages[2]["minVersion"],
+            Value::from("4.15.0-202402082307")
+        );
+        assert_eq!(packages[2].get("bundles"), None);
+
+        // not an isc
+        assert_eq!(merge_isc("kind: Other".to_string(), &isc).is_err(), true);
+
+        // unexpected types are reported, not panicking
+        for existing in [
+            "kind: ImageSetConfiguration\nmirror:\n  operators: foo\n",
+            "kind: ImageSetConfiguration\nmirror:\n  operators:\n  - catalog: registry.redhat.io/redhat/redhat-operator-index:v4.15\n    packages: foo\n",
+        ] {
+            assert_eq!(merge_isc(existing.to_string(), &isc).is_err(), true);
+        }
+        // null sections are replaced
+        let existing = "kind: ImageSetConfiguration\nmirror:\n  operators:\n  - catalog: registry.redhat.io/redhat/redhat-operator-index:v4.15\n    packages:\n    - name: amq-streams\n      bundles: ~\n";
+        assert_eq!(merge_isc(existing.to_string(), &isc).is_ok(), true);
+
+        assert_eq!(has_comments("kind: ImageSetConfiguration # isc"), true);
+        assert_eq!(
+            has_comments("  # curated\nkind: ImageSetConfiguration"),
+            true
+        );
+        assert_eq!(has_comments("kind: ImageSetConfiguration"), false);
+    }
+}
@@ -1 +1,3 @@
 pub mod generate;
+pub mod merge;
+pub mod output;
@@ -0,0 +1,240 @@
+use crate::isc::generate::*;
+use crate::isc::merge::*;
+use custom_logger::*;
+use mirror_error::MirrorError;
+use std::fs;
+use std::io::{self, Write};
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::path::Path;
+
+pub const DEFAULT_OUTPUT_FILE: &str = "auto-generated.yaml";
+
+// where and how the generated isc is written
+#[derive(Debug, Clone)]
+pub struct IscOutput {
+    pub output_dir: String,
+    pub output_file: Option<String>,
+    pub merge_into: Option<String>,
+    pub per_catalog: bool,
+    pub stdout: bool,
+    pub force: bool,
+}
+
+// write the isc (one file or one file per catalog) or print it to stdout
+// existing files are only overwritten when force is set
+pub fn write_isc(log: &Logging, isc: &IscConfig, opts: &IscOutput) -> Result<(), MirrorError> {
+    let mut documents: Vec<(String, String)> = vec![];
+    if opts.merge_into.is_some() {
+        // update the existing isc in place
+        let file = opts.merge_into.clone().unwrap();
+        let res = fs::read_to_string(&file);
+        if res.is_err() {
+            return Err(MirrorError::new(&format!(
+                "[write_isc] reading {} {}",
+                file,
+                res.err().unwrap().to_string().to_lowercase()
+            )));
+        }
+        let existing = res.unwrap();
+        // the existing file is rewritten in place, so it is held to the same --force check
+        // (the merged document can't keep the yaml comments)
+        let comments = has_comments(&existing);
+        if !opts.force && !opts.stdout {
+            let reason = match comments {
+                true => "its yaml comments would be dropped",
+                false => "it is rewritten in place",
+            };
+            return Err(MirrorError::new(&format!(
+                "[write_isc] not merging into {}, {} (use --force to overwrite it or --stdout)",
+                file, reason
+            )));
+        }
+        if comments {
+            log.warn(&format!(
+                "[write_isc] the yaml comments in {} are not kept by the merge",
+                file
+            ));
+        }
+        documents.push((file.clone(), merge_isc(existing, isc)?));
+    } else if opts.per_catalog {
+        for (i, ctlog) in isc.operators.iter().enumerate() {
+            let mut single = isc.clone();
+            single.operators = vec![ctlog.clone()];
+            // platform and additional images are only written to the first file
+            if i > 0 {
+                single.platform = None;
+                single.additional_images = vec![];
+            }
+            documents.push((
+                format!("{}/{}", opts.output_dir, catalog_file_name(&ctlog.catalog)),
+                single.to_yaml(),
+            ));
+        }
+    } else {
+        let file = opts
+            .output_file
+            .clone()
+            .unwrap_or(DEFAULT_OUTPUT_FILE.to_string());
+        documents.push((format!("{}/{}", opts.output_dir, file), isc.to_yaml()));
+    }
+
+    if opts.stdout {
+        for (_, contents) in documents.iter() {
+            print!("{}", contents);
+        }
+        return Ok(());
+    }
+
+    // check all files before writing anything
+    if !opts.force {
+        for (file, _) in documents.iter() {
+            if Path::new(file).exists() {
+                return Err(MirrorError::new(&format!(
+                    "[write_isc] file {} already exists (use --force to overwrite)",
+                    file
+                )));
+            }
+        }
+    }
+    for (file, contents) in documents.iter() {
+        log.info(&format!("{}", contents));
+        let res = fs::write(file, contents);
+        if res.is_err() {
+            return Err(MirrorError::new(&format!(
+                "[write_isc] writing {} {}",
+                file,
+                res.err().unwrap().to_string().to_lowercase()
+            )));
+        }
+        log.info(&format!("[write_isc] saved {}", file));
+    }
+    Ok(())
+}
+
+// while held, everything written to stdout (logger and report) goes to stderr
+// so only the imagesetconfig printed with --stdout ends up on stdout
+pub struct StdoutRedirect {
+    stdout: OwnedFd,
+}
+
+impl StdoutRedirect {
+    pub fn new() -> Result<Self, MirrorError> {
+        let _ = io::stdout().flush();
+        let res = io::stdout().as_fd().try_clone_to_owned();
+        if res.is_err() {
+            return Err(MirrorError::new(&format!(
+                "[StdoutRedirect] duplicating stdout {}",
+                res.err().unwrap().to_string().to_lowercase()
+            )));
+        }
+        let stdout = res.unwrap();
+        // safety: both descriptors are open for the lifetime of the process
+        if unsafe { libc::dup2(io::stderr().as_raw_fd(), io::stdout().as_raw_fd()) } < 0 {
+            return Err(MirrorError::new(&format!(
+                "[StdoutRedirect] redirecting stdout {}",
+                io::Error::last_os_error().to_string().to_lowercase()
+            )));
+        }
+        Ok(StdoutRedirect { stdout })
+    }
+}
+
+impl Drop for StdoutRedirect {
+    fn drop(&mut self) {
+        let _ = io::stdout().flush();
+        // safety: the saved descriptor is owned by self and still open
+        unsafe {
+            libc::dup2(self.stdout.as_raw_fd(), io::stdout().as_raw_fd());
+        }
+    }
+}
+
+// file name for a catalog i.e registry.redhat.io/redhat/redhat-operator-index:v4.15
+// -> redhat-operator-index-v4.15.yaml
+pub fn catalog_file_name(catalog: &str) -> String {
+    let last = catalog.split('/').last().unwrap();
+    format!("{}.yaml", last.replace(":", "-").replace("@", "-"))
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+
+    #[test]
+    fn write_isc_pass() {
+        let log = &Logging {
+            log_level: Level::INFO,
+        };
+        let output_dir = "./test-artifacts/isc-output".to_string();
+        fs::create_dir_all(&output_dir).expect("should create isc output test folder");
+
+        let mut isc = IscConfig::new("v2alpha1".to_string());
+        isc.additional_images = vec![Image {
+            name: "registry.redhat.io/ubi8/ubi:latest".to_string(),
+        }];
+        for tag in ["v4.14", "v4.15"] {
+            isc.operators.push(Catalog {
+                catalog: format!("registry.redhat.io/redhat/redhat-operator-index:{}", tag),
+                packages: vec![Package {
+                    name: "amq-streams".to_string(),
+                    ..Default::default()
+                }],
+            });
+        }
+        let mut opts = IscOutput {
+            output_dir: output_dir.clone(),
+            output_file: None,
+            merge_into: None,
+            per_catalog: true,
+            stdout: false,
+            force: false,
+        };
+        assert_eq!(write_isc(log, &isc, &opts).is_ok(), true);
+        let file = format!("{}/redhat-operator-index-v4.15.yaml", output_dir);
+        let contents = fs::read_to_string(&file).expect("should read per catalog isc");
+        assert_eq!(contents.contains("redhat-operator-index:v4.15"), true);
+        assert_eq!(contents.contains("redhat-operator-index:v4.14"), false);
+        // additional images are only in the first file
+        assert_eq!(contents.contains("additionalImages"), false);
+        let file = format!("{}/redhat-operator-index-v4.14.yaml", output_dir);
+        let contents = fs::read_to_string(&file).expect("should read per catalog isc");
+        assert_eq!(contents.contains("ubi8/ubi:latest"), true);
+
+        // refuse to overwrite unless forced
+        assert_eq!(write_isc(log, &isc, &opts).is_err(), true);
+        opts.force = true;
+        assert_eq!(write_isc(log, &isc, &opts).is_ok(), true);
+
+        // merging rewrites the file in place, also held to the force check
+        let opts = IscOutput {
+            output_dir: output_dir.clone(),
+            output_file: None,
+            merge_into: Some(file.clone()),
+            per_catalog: false,
+            stdout: false,
+            force: false,
+        };
+        assert_eq!(write_isc(log, &isc, &opts).is_err(), true);
+        let opts = IscOutput {
+            force: true,
+            ..opts
+        };
+        assert_eq!(write_isc(log, &isc, &opts).is_ok(), true);
+        let contents = fs::read_to_string(&file).expect("should read merged isc");
+        assert_eq!(contents.contains("redhat-operator-index:v4.15"), true);
+
+        // comments are never dropped without --force
+        let commented = format!("# curated by hand\n{}", contents);
+        fs::write(&file, &commented).expect("should write commented isc");
+        let opts = IscOutput {
+            force: false,
+            ..opts
+        };
+        let err = write_isc(log, &isc, &opts).err().unwrap();
+        assert!(err.to_string().contains("yaml comments would be dropped"));
+        assert_eq!(fs::read_to_string(&file).unwrap(), commented);
+
+        fs::remove_dir_all(&output_dir).expect("should delete isc output test folder");
+    }
+}
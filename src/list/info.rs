@@ -0,0 +1,231 @@
+use crate::fbc::reader::*;
+use custom_logger::*;
+use mirror_error::MirrorError;
+use serde_json::Value;
+
+// operator details from the package and the head bundle metadata
+#[derive(Debug, Clone, Default)]
+pub struct OperatorInfo {
+    pub package: String,
+    pub display_name: String,
+    pub description: String,
+    pub provider: String,
+    pub maturity: String,
+    pub capabilities: String,
+    pub install_modes: Vec<String>,
+    pub min_kube_version: String,
+    pub owned_crds: Vec<String>,
+    pub channel: String,
+    pub head_bundle: String,
+    pub version: String,
+    pub bundle_image: String,
+}
+
+// read the operator details for the given channel (defaults to the package default channel)
+pub async fn get_operator_info(
+    log: &Logging,
+    dir: String,
+    catalog: String,
+    operator: String,
+    channel: Option<String>,
+) -> Result<OperatorInfo, MirrorError> {
+    let config_dir = get_configs_dir(log, dir, catalog.clone()).await?;
+    let blobs = read_package_blobs(config_dir, operator.clone())?;
+    let info = build_operator_info(&blobs, catalog, operator, channel)?;
+    log.trace(&format!("[get_operator_info] {:#?}", info));
+    Ok(info)
+}
+
+// build the operator details from the package blobs
+pub fn build_operator_info(
+    blobs: &Vec<Value>,
+    catalog: String,
+    operator: String,
+    channel: Option<String>,
+) -> Result<OperatorInfo, MirrorError> {
+    let pkg = get_package(blobs);
+    if pkg.is_none() {
+        return Err(MirrorError::new(&format!(
+            "[build_operator_info] operator {} not found in catalog {}",
+            operator, catalog
+        )));
+    }
+    let pkg = pkg.unwrap();
+    let channel_name = channel.unwrap_or(pkg.default_channel.clone().unwrap_or_default());
+    let channels = get_channels(blobs);
+    let ch = channels.iter().find(|c| c.name == channel_name);
+    if ch.is_none() {
+        return Err(MirrorError::new(&format!(
+            "[build_operator_info] channel {} not found for operator {}",
+            channel_name, operator
+        )));
+    }
+    let head = ch.unwrap().head().unwrap_or_default();
+    let bundles = get_bundles(blobs);
+    let bundle = bundles
+        .iter()
+        .find(|b| b.name == head)
+        .cloned()
+        .unwrap_or_default();
+    let metadata = bundle.csv_metadata().unwrap_or(Value::Null);
+
+    let mut info = OperatorInfo {
+        package: pkg.name.clone(),
+        display_name: as_string(&metadata["displayName"]),
+        description: as_string(&metadata["description"]),
+        provider: as_string(&metadata["provider"]["name"]),
+        maturity: as_string(&metadata["maturity"]),
+        capabilities: as_string(&metadata["annotations"]["capabilities"]),
+        install_modes: vec![],
+        min_kube_version: as_string(&metadata["minKubeVersion"]),
+        owned_crds: vec![],
+        channel: channel_name,
+        head_bundle: head,
+        version: bundle.version().unwrap_or_default(),
+        bundle_image: bundle.image.clone().unwrap_or_default(),
+    };
+    if info.description.is_empty() {
+        info.description = pkg.description.clone().unwrap_or_default();
+    }
+    for m in metadata["installModes"]
+        .as_array()
+        .unwrap_or(&vec![])
+        .iter()
+    {
+        if m["supported"] == true {
+            info.install_modes.push(as_string(&m["type"]));
+        }
+    }
+    for crd in metadata["crdDescriptions"]["owned"]
+        .as_array()
+        .unwrap_or(&vec![])
+        .iter()
+    {
+        info.owned_crds.push(format!(
+            "{} ({})",
+            as_string(&crd["name"]),
+            as_string(&crd["version"])
+        ));
+    }
+    // older bundles without csv metadata, fall back to the provided apis
+    if info.owned_crds.is_empty() {
+        for gvk in bundle.get_properties("olm.gvk").iter() {
+            info.owned_crds.push(format!(
+                "{}/{} {}",
+                as_string(&gvk["group"]),
+                as_string(&gvk["version"]),
+                as_string(&gvk["kind"])
+            ));
+        }
+    }
+    Ok(info)
+}
+
+// display the operator details
+pub fn render_info(info: &OperatorInfo) {
+    let rows = vec![
+        ("DISPLAY NAME", info.display_name.clone()),
+        ("PROVIDER", info.provider.clone()),
+        ("CHANNEL", info.channel.clone()),
+        ("HEAD BUNDLE", info.head_bundle.clone()),
+        ("VERSION", info.version.clone()),
+        ("BUNDLE IMAGE", info.bundle_image.clone()),
+        ("MATURITY", info.maturity.clone()),
+        ("CAPABILITIES", info.capabilities.clone()),
+        ("INSTALL MODES", info.install_modes.join(", ")),
+        ("MIN KUBE VERSION", info.min_kube_version.clone()),
+    ];
+    println!();
+    println!("\x1b[05C\x1b[1;97m{}\x1b[0m", info.package);
+    println!("\x1b[05C\x1b[1;97m-----------------------------------------\x1b[0m");
+    for (k, v) in rows.iter() {
+        println!("\x1b[05C\x1b[1;97m{:<20}\x1b[0m\x1b[0;97m{}\x1b[0m", k, v);
+    }
+    println!("\x1b[05C\x1b[1;97m{:<20}\x1b[0m", "OWNED CRDS");
+    for crd in info.owned_crds.iter() {
+        println!("\x1b[25C\x1b[0;97m{}\x1b[0m", crd);
+    }
+    println!("\x1b[05C\x1b[1;97m{:<20}\x1b[0m", "DESCRIPTION");
+    for line in info.description.lines() {
+        println!("\x1b[25C\x1b[0;97m{}\x1b[0m", line);
+    }
+    println!();
+}
+
+fn as_string(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+
+    #[test]
+    fn build_operator_info_pass() {
+        let blobs: Vec<Value> = serde_json::from_str(
+            "[
+            { \"schema\": \"olm.package\", \"name\": \"test-operator\", \"defaultChannel\": \"stable\", \"description\": \"package description\" },
+            { \"schema\": \"olm.channel\", \"name\": \"stable\", \"package\": \"test-operator\",
+              \"entries\": [ { \"name\": \"test-operator.v0.0.1\" }, { \"name\": \"test-operator.v0.0.2\", \"replaces\": \"test-operator.v0.0.1\" } ] },
+            { \"schema\": \"olm.channel\", \"name\": \"alpha\", \"package\": \"test-operator\",
+              \"entries\": [ { \"name\": \"test-operator.v0.0.1\" } ] },
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.1\", \"package\": \"test-operator\",
+              \"image\": \"quay.io/test/bundle@sha256:01\",
+              \"properties\": [
+                { \"type\": \"olm.package\", \"value\": { \"packageName\": \"test-operator\", \"version\": \"0.0.1\" } },
+                { \"type\": \"olm.gvk\", \"value\": { \"group\": \"test.io\", \"version\": \"v1\", \"kind\": \"Test\" } } ] },
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.2\", \"package\": \"test-operator\",
+              \"image\": \"quay.io/test/bundle@sha256:02\",
+              \"properties\": [
+                { \"type\": \"olm.package\", \"value\": { \"packageName\": \"test-operator\", \"version\": \"0.0.2\" } },
+                { \"type\": \"olm.csv.metadata\", \"value\": {
+                    \"displayName\": \"Test Operator\", \"description\": \"csv description\",
+                    \"provider\": { \"name\": \"Test\" }, \"maturity\": \"stable\",
+                    \"annotations\": { \"capabilities\": \"Seamless Upgrades\" },
+                    \"installModes\": [ { \"type\": \"OwnNamespace\", \"supported\": true }, { \"type\": \"MultiNamespace\", \"supported\": false } ],
+                    \"minKubeVersion\": \"1.25.0\",
+                    \"crdDescriptions\": { \"owned\": [ { \"name\": \"tests.test.io\", \"version\": \"v1\", \"kind\": \"Test\" } ] } } } ] }
+            ]",
+        )
+        .expect("should parse blobs");
+
+        let info = build_operator_info(
+            &blobs,
+            "test-index:v0.0.1".to_string(),
+            "test-operator".to_string(),
+            None,
+        )
+        .expect("should build operator info");
+        assert_eq!(info.channel, "stable");
+        assert_eq!(info.head_bundle, "test-operator.v0.0.2");
+        assert_eq!(info.version, "0.0.2");
+        assert_eq!(info.bundle_image, "quay.io/test/bundle@sha256:02");
+        assert_eq!(info.description, "csv description");
+        assert_eq!(info.provider, "Test");
+        assert_eq!(info.capabilities, "Seamless Upgrades");
+        assert_eq!(info.install_modes, vec!["OwnNamespace"]);
+        assert_eq!(info.min_kube_version, "1.25.0");
+        assert_eq!(info.owned_crds, vec!["tests.test.io (v1)"]);
+
+        // no csv metadata, falls back to the package description and provided apis
+        let info = build_operator_info(
+            &blobs,
+            "test-index:v0.0.1".to_string(),
+            "test-operator".to_string(),
+            Some("alpha".to_string()),
+        )
+        .expect("should build operator info");
+        assert_eq!(info.head_bundle, "test-operator.v0.0.1");
+        assert_eq!(info.description, "package description");
+        assert_eq!(info.owned_crds, vec!["test.io/v1 Test"]);
+
+        let res = build_operator_info(
+            &blobs,
+            "test-index:v0.0.1".to_string(),
+            "test-operator".to_string(),
+            Some("fast".to_string()),
+        );
+        assert!(res.is_err());
+    }
+}
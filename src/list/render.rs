@@ -1,3 +1,4 @@
+use crate::fbc::reader::*;
 use custom_logger::*;
 use mirror_catalog::*;
 use mirror_catalog_index::find_dir;
@@ -17,6 +18,9 @@ pub async fn render_list(
     let catalog_dir = format!("{}/{}/{}", dir.clone(), &index_dir, "/amd64/cache/");
 
     if operator.is_none() {
+        let mut deprecated = false;
+        let config_dir = find_dir(log, catalog_dir.clone(), "configs".to_string()).await;
+        let deprecations = catalog_deprecations(config_dir);
         let result = WalkDir::new(&catalog_dir);
         println!("");
         println!("\x1b[05C{}", "\x1b[1;97mOPERATORS\x1b[0m");
@@ -30,10 +34,14 @@ pub async fn render_list(
                 if check.path().is_dir() {
                     let f = file.unwrap().clone().path().display().to_string();
                     if f.contains("/configs/") && !f.contains("/updated-configs") {
-                        println!(
-                            "\x1b[05C\x1b[0;97m{}\x1b[0m",
-                            f.split("/configs/").nth(1).unwrap()
-                        );
+                        let name = f.split("/configs/").nth(1).unwrap();
+                        // deprecated packages are highlighted in yellow
+                        if deprecations.get(name).and_then(|d| d.package()).is_some() {
+                            deprecated = true;
+                            println!("\x1b[05C\x1b[0;93m{} *\x1b[0m", name);
+                        } else {
+                            println!("\x1b[05C\x1b[0;97m{}\x1b[0m", name);
+                        }
                     }
                 }
             } else {
@@ -44,6 +52,10 @@ pub async fn render_list(
                 return Err(err);
             }
         }
+        if deprecated {
+            println!("");
+            println!("\x1b[05C\x1b[0;93m* deprecated (use --operator for details)\x1b[0m");
+        }
     } else {
         let config_dir = find_dir(log, catalog_dir.clone(), "configs".to_string()).await;
         let operator_file = format!(
@@ -52,6 +64,9 @@ pub async fn render_list(
             operator.as_ref().unwrap()
         );
         let dc_map = DeclarativeConfig::get_declarativeconfig_map(operator_file);
+        let blobs =
+            read_package_blobs(config_dir.clone(), operator.clone().unwrap()).unwrap_or(vec![]);
+        let deprecations = get_deprecations(&blobs);
         let mut default_channel: String = String::new();
         let keys = dc_map.keys();
         println!("");
@@ -64,12 +79,24 @@ pub async fn render_list(
                 break;
             }
         }
-        println!("\x1b[05C\x1b[1;97m{}\x1b[0m\x1b[1A", operator.unwrap());
+        if deprecations.package().is_some() {
+            println!(
+                "\x1b[05C\x1b[1;93m{} *\x1b[0m\x1b[1A",
+                operator.clone().unwrap()
+            );
+        } else {
+            println!(
+                "\x1b[05C\x1b[1;97m{}\x1b[0m\x1b[1A",
+                operator.clone().unwrap()
+            );
+        }
         for k in keys {
             if k.contains("olm.channel") {
                 let channel = dc_map.get(k).unwrap();
                 let name = channel.name.as_ref().unwrap().to_string();
-                if name == default_channel {
+                if deprecations.channel(&name).is_some() {
+                    println!("\x1b[55C\x1b[1;93m{} *\x1b[0m\x1b[1A", name);
+                } else if name == default_channel {
                     println!(
                         "\x1b[55C\x1b[1;94m{}\x1b[0m\x1b[1A",
                         channel.name.as_ref().unwrap()
@@ -78,11 +105,29 @@ pub async fn render_list(
                     println!("\x1b[55C{}\x1b[1A", channel.name.as_ref().unwrap());
                 }
                 for e in channel.entries.as_ref().unwrap().iter() {
-                    println!("\x1b[85C\x1b[0;97m{}\x1b[0m", e.name);
+                    if deprecations.bundle(&e.name).is_some() {
+                        println!("\x1b[85C\x1b[0;93m{} *\x1b[0m", e.name);
+                    } else {
+                        println!("\x1b[85C\x1b[0;97m{}\x1b[0m", e.name);
+                    }
                 }
                 println!("");
             }
         }
+        // display the deprecation messages below the table
+        if deprecations.entries.len() > 0 {
+            println!("\x1b[05C\x1b[1;93mDEPRECATIONS (*)\x1b[0m");
+            for e in deprecations.entries.iter() {
+                let kind = e.reference.schema.trim_start_matches("olm.");
+                let name = e
+                    .reference
+                    .name
+                    .clone()
+                    .unwrap_or(operator.clone().unwrap());
+                log.warn(&format!("{} {} : {}", kind, name, e.message.trim()));
+            }
+            println!("");
+        }
     }
     Ok(())
 }
@@ -0,0 +1,121 @@
+use custom_logger::*;
+use mirror_error::MirrorError;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::Path;
+
+const LOCK_FILE: &str = ".catalog-introspection.lock";
+
+// advisory lock on a working directory
+// the lock is released when the struct is dropped (or the process exits)
+#[derive(Debug)]
+pub struct CacheLock {
+    file: File,
+}
+
+impl Drop for CacheLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+// acquire an exclusive (writers i.e update) or shared (readers i.e list, upgradepath)
+// lock on the working directory, if wait is false fail fast when the lock is held
+pub fn acquire_lock(
+    log: &Logging,
+    dir: String,
+    exclusive: bool,
+    wait: bool,
+) -> Result<CacheLock, MirrorError> {
+    if !Path::new(&dir).exists() {
+        return Err(MirrorError::new(&format!(
+            "[acquire_lock] working directory {} does not exist",
+            dir
+        )));
+    }
+    let path = format!("{}/{}", dir, LOCK_FILE);
+    let res = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path);
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[acquire_lock] opening lock file {} {}",
+            path,
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    let mut file = res.unwrap();
+    let mode = if exclusive { "exclusive" } else { "shared" };
+    log.debug(&format!("[acquire_lock] requesting {} lock {}", mode, path));
+
+    let res_lock = match (exclusive, wait) {
+        (true, true) => file.lock().map_err(TryLockError::Error),
+        (false, true) => file.lock_shared().map_err(TryLockError::Error),
+        (true, false) => file.try_lock(),
+        (false, false) => file.try_lock_shared(),
+    };
+    match res_lock {
+        Ok(_) => {}
+        Err(TryLockError::WouldBlock) => {
+            return Err(MirrorError::new(&format!(
+                "[acquire_lock] working directory {} is locked by another process (use --wait to wait for the lock to be released)",
+                dir
+            )));
+        }
+        Err(TryLockError::Error(e)) => {
+            return Err(MirrorError::new(&format!(
+                "[acquire_lock] locking {} {}",
+                path,
+                e.to_string().to_lowercase()
+            )));
+        }
+    }
+
+    if exclusive {
+        // record the current owner, useful when debugging a held lock
+        let _ = file.set_len(0);
+        let _ = writeln!(file, "{}", std::process::id());
+    }
+    log.trace(&format!("[acquire_lock] acquired {} lock {}", mode, path));
+    Ok(CacheLock { file })
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn acquire_lock_pass() {
+        let log = &Logging {
+            log_level: Level::DEBUG,
+        };
+        let dir = "./test-artifacts/lock".to_string();
+        fs::create_dir_all(&dir).expect("should create lock test folder");
+
+        // readers can share the lock
+        let reader_a = acquire_lock(log, dir.clone(), false, false);
+        let reader_b = acquire_lock(log, dir.clone(), false, false);
+        assert_eq!(reader_a.is_ok(), true);
+        assert_eq!(reader_b.is_ok(), true);
+
+        // a writer is refused while readers hold the lock
+        let writer = acquire_lock(log, dir.clone(), true, false);
+        assert_eq!(writer.is_err(), true);
+        drop(reader_a);
+        drop(reader_b);
+
+        // and readers are refused while a writer holds it
+        let writer = acquire_lock(log, dir.clone(), true, false);
+        assert_eq!(writer.is_ok(), true);
+        let reader = acquire_lock(log, dir.clone(), false, false);
+        assert_eq!(reader.is_err(), true);
+        drop(writer);
+
+        fs::remove_dir_all(&dir).expect("should delete lock test folder");
+    }
+}
@@ -11,17 +11,38 @@ use tokio;
 // define local modules
 mod api;
 mod batch;
+mod browse;
 mod config;
+mod fbc;
 mod isc;
 mod list;
+mod lock;
 mod operator;
+mod registry;
+mod related;
+mod search;
+mod serve;
+mod sizing;
 mod upgradepath;
 
 // use local modules
 use api::schema::*;
+use browse::ui::*;
 use config::read::*;
+use config::validate::*;
+use fbc::reader::*;
+use isc::output::*;
+use list::info::*;
 use list::render::*;
+use lock::cache::*;
 use operator::collector::*;
+use operator::permissions::*;
+use registry::catalog::*;
+use registry::server::*;
+use related::images::*;
+use search::query::*;
+use serve::server::*;
+use sizing::estimate::*;
 use upgradepath::calculate::*;
 
 // main entry point (use async)
@@ -31,21 +52,22 @@ async fn main() -> Result<(), MirrorError> {
 
     let lvl = args.loglevel.as_ref().unwrap();
 
-    let l = match lvl.as_str() {
-        "info" => Level::INFO,
-        "debug" => Level::DEBUG,
-        "trace" => Level::TRACE,
-        _ => Level::INFO,
+    let log = &Logging {
+        log_level: log_level(lvl),
     };
 
-    let log = &Logging { log_level: l };
-
     match &args.command {
         Some(Commands::List {
             working_dir,
             catalog,
             operator,
+            wait,
         }) => {
+            let lock = acquire_lock(log, working_dir.clone(), false, *wait);
+            if lock.is_err() {
+                log.error(&format!("[main] {}", lock.err().unwrap().to_string()));
+                process::exit(1);
+            }
             let res =
                 render_list(log, working_dir.clone(), catalog.clone(), operator.clone()).await;
             if res.is_err() {
@@ -60,10 +82,21 @@ async fn main() -> Result<(), MirrorError> {
         Some(Commands::Update {
             working_dir,
             config_file,
+            cache_mode,
+            cache_owner,
+            read_only_cache,
+            wait,
         }) => {
+            let perms =
+                CachePermissions::new(cache_mode.clone(), *read_only_cache, cache_owner.clone())?;
             // Parse the config serde_yaml::FilterConfiguration.
             let res_config = load_config(config_file.to_string()).await?;
-            let res_fc = parse_yaml_config(res_config)?;
+            let res_fc = parse_and_validate_config(config_file.to_string(), res_config);
+            if res_fc.is_err() {
+                log.error(&format!("[main] {}", res_fc.err().unwrap().to_string()));
+                process::exit(1);
+            }
+            let res_fc = res_fc.unwrap();
 
             log.debug(&format!("{:#?}", res_fc.operators.clone()));
 
@@ -95,6 +128,8 @@ async fn main() -> Result<(), MirrorError> {
                     ));
                     return Err(err);
                 }
+                // only one update may write to the working-dir at a time
+                let _lock = acquire_lock(log, working_dir.clone(), true, *wait)?;
                 // quickly convert to Operator struct
                 let mut operators = vec![];
                 for op in res_fc.catalogs.clone() {
@@ -111,14 +146,22 @@ async fn main() -> Result<(), MirrorError> {
                     false,
                     true,
                     operators,
+                    perms,
                 )
                 .await;
                 if res.is_err() {
-                    let err = MirrorError::new(&format!(
-                        "creating directory {}",
-                        res.err().unwrap().to_string().to_lowercase()
+                    log.error(&format!(
+                        "[main] update failed {}",
+                        res.err().unwrap().to_string()
                     ));
-                    return Err(err);
+                    process::exit(1);
+                }
+                // the cache is keyed on name:version, keep the full reference for the exports
+                for catalog in res_fc.catalogs.iter() {
+                    let res = save_catalog_reference(working_dir.clone(), catalog.clone());
+                    if res.is_err() {
+                        log.warn(&format!("[main] {}", res.err().unwrap().to_string()));
+                    }
                 }
             }
         }
@@ -126,22 +169,223 @@ async fn main() -> Result<(), MirrorError> {
             config_file,
             working_dir,
             output_dir,
+            output_file,
+            merge_into,
+            per_catalog,
+            stdout,
+            force,
             api_version,
+            isc_style,
+            strict,
+            skip_dependencies,
+            target_ocp,
+            exclude_deprecated,
+            graph,
+            related_images,
+            estimate_size,
+            wait,
         }) => {
-            // create artifacts directory
-            fs_handler(output_dir.clone(), "create_dir", None).await?;
+            // with --stdout only the imagesetconfig is written to stdout, the report goes to stderr
+            let redirect = if *stdout {
+                Some(StdoutRedirect::new()?)
+            } else {
+                None
+            };
+            let _lock = acquire_lock(log, working_dir.clone(), false, *wait)?;
+            // create artifacts directory (not needed when only printing the imagesetconfig)
+            if !*stdout || *related_images || graph.is_some() {
+                fs_handler(output_dir.clone(), "create_dir", None).await?;
+            }
             // Parse the config serde_yaml::FilterConfiguration.
             let res_config = load_config(config_file.to_string()).await?;
-            let res_fc = parse_yaml_config(res_config)?;
+            let res_fc = parse_and_validate_config(config_file.to_string(), res_config);
+            if res_fc.is_err() {
+                log.error(&format!("[main] {}", res_fc.err().unwrap().to_string()));
+                process::exit(1);
+            }
+            let res_fc = res_fc.unwrap();
 
-            process_upgradepath(
+            let upgrade_opts = UpgradepathOptions {
+                isc_style: isc_style.to_string(),
+                strict: *strict,
+                skip_dependencies: *skip_dependencies,
+                target_ocp: target_ocp.clone(),
+                exclude_deprecated: *exclude_deprecated,
+                graph: graph.clone(),
+                output_dir: output_dir.to_string(),
+            };
+            let isc = process_upgradepath(
                 log,
                 api_version.to_string(),
                 working_dir.to_string(),
-                output_dir.to_string(),
                 res_fc.clone(),
+                &upgrade_opts,
+            )
+            .await;
+            if isc.is_err() {
+                log.error(&format!("[main] {}", isc.err().unwrap().to_string()));
+                process::exit(1);
+            }
+            let isc = isc.unwrap();
+            let opts = IscOutput {
+                output_dir: output_dir.to_string(),
+                output_file: output_file.clone(),
+                merge_into: merge_into.clone(),
+                per_catalog: *per_catalog,
+                stdout: *stdout,
+                force: *force,
+            };
+            let write = |isc| {
+                let res = write_isc(log, isc, &opts);
+                if res.is_err() {
+                    log.error(&format!("[main] {}", res.err().unwrap().to_string()));
+                    process::exit(1);
+                }
+            };
+            if !*stdout {
+                write(&isc);
+            }
+            if *related_images {
+                let res = process_related_images(
+                    log,
+                    working_dir.to_string(),
+                    output_dir.to_string(),
+                    &isc,
+                )
+                .await;
+                if res.is_err() {
+                    log.error(&format!("[main] {}", res.err().unwrap().to_string()));
+                    process::exit(1);
+                }
+            }
+            if *estimate_size {
+                let reg_con = ImplDownloadImageInterface {};
+                let res =
+                    process_estimate_size(reg_con, log, working_dir.to_string(), true, &isc).await;
+                if res.is_err() {
+                    log.error(&format!("[main] {}", res.err().unwrap().to_string()));
+                    process::exit(1);
+                }
+            }
+            if *stdout {
+                drop(redirect);
+                write(&isc);
+            }
+        }
+        Some(Commands::Search {
+            working_dir,
+            query,
+            wait,
+        }) => {
+            let _lock = acquire_lock(log, working_dir.clone(), false, *wait)?;
+            let res = search_catalogs(log, working_dir.clone(), query.clone()).await;
+            if res.is_err() {
+                log.error(&format!("[main] {}", res.err().unwrap().to_string()));
+                process::exit(1);
+            }
+            let results = res.unwrap();
+            if results.len() == 0 {
+                log.warn(&format!("[main] no packages found matching '{}'", query));
+            } else {
+                render_search(&results);
+            }
+        }
+        Some(Commands::Info {
+            working_dir,
+            catalog,
+            operator,
+            channel,
+            wait,
+        }) => {
+            let _lock = acquire_lock(log, working_dir.clone(), false, *wait)?;
+            let res = get_operator_info(
+                log,
+                working_dir.clone(),
+                catalog.clone(),
+                operator.clone(),
+                channel.clone(),
             )
             .await;
+            if res.is_err() {
+                log.error(&format!("[main] {}", res.err().unwrap().to_string()));
+                process::exit(1);
+            }
+            render_info(&res.unwrap());
+        }
+        Some(Commands::Browse {
+            working_dir,
+            output_dir,
+            wait,
+        }) => {
+            let _lock = acquire_lock(log, working_dir.clone(), false, *wait)?;
+            let res = run_browse(log, working_dir.clone(), output_dir.clone());
+            if res.is_err() {
+                log.error(&format!("[main] {}", res.err().unwrap().to_string()));
+                process::exit(1);
+            }
+        }
+        Some(Commands::Serve {
+            working_dir,
+            address,
+        }) => {
+            // the server owns its own logger, requests are handled on other tasks
+            let serve_log = Logging {
+                log_level: log_level(lvl),
+            };
+            let res = run_serve(serve_log, working_dir.clone(), address.clone()).await;
+            if res.is_err() {
+                log.error(&format!("[main] {}", res.err().unwrap().to_string()));
+                process::exit(1);
+            }
+        }
+        Some(Commands::Registry {
+            working_dir,
+            catalog,
+            address,
+            wait,
+        }) => {
+            // the catalog is served from memory, the lock is only held while loading
+            let res = {
+                let _lock = acquire_lock(log, working_dir.clone(), false, *wait)?;
+                load_registry_catalog(log, working_dir.clone(), catalog.clone())
+            };
+            if res.is_err() {
+                log.error(&format!("[main] {}", res.err().unwrap().to_string()));
+                process::exit(1);
+            }
+            let res = run_registry(log, res.unwrap(), address.clone()).await;
+            if res.is_err() {
+                log.error(&format!("[main] {}", res.err().unwrap().to_string()));
+                process::exit(1);
+            }
+        }
+        Some(Commands::Validate { config_file }) => {
+            let mut failed = false;
+            for file in config_file.iter() {
+                // an unreadable file is reported and the others are still validated
+                let res = load_config(file.to_string()).await;
+                if res.is_err() {
+                    log.error(&format!(
+                        "{}: {}",
+                        file,
+                        res.err().unwrap().to_string().to_lowercase()
+                    ));
+                    failed = true;
+                    continue;
+                }
+                let data = res.unwrap();
+                let issues = validate_filter_config(&data);
+                if issues.len() == 0 {
+                    log.info(&format!("[validate] {} is valid", file));
+                }
+                for issue in issues.iter() {
+                    log.error(&issue.to_string(file));
+                    failed = true;
+                }
+            }
+            if failed {
+                process::exit(1);
+            }
         }
         None => {
             log.error(
@@ -152,3 +396,12 @@ async fn main() -> Result<(), MirrorError> {
     }
     Ok(())
 }
+
+fn log_level(lvl: &str) -> Level {
+    match lvl {
+        "info" => Level::INFO,
+        "debug" => Level::DEBUG,
+        "trace" => Level::TRACE,
+        _ => Level::INFO,
+    }
+}
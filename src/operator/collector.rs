@@ -1,4 +1,5 @@
 use crate::batch::worker::execute_batch;
+use crate::operator::permissions::*;
 use custom_logger::*;
 use mirror_auth::*;
 use mirror_catalog::*;
@@ -11,6 +12,7 @@ use mirror_utils::{
     process_and_update_manifest, FsLayer,
 };
 use std::collections::HashMap;
+use std::fs;
 use std::fs::DirBuilder;
 use std::os::unix::fs::DirBuilderExt;
 use std::path::Path;
@@ -23,6 +25,7 @@ pub async fn get_operator_catalog<T: DownloadImageInterface + Clone>(
     _all_arch: bool,
     token_enable: bool,
     operators: Vec<Operator>,
+    perms: CachePermissions,
 ) -> Result<(), MirrorError> {
     log.hi("[get_operator_catalog] collector");
     // set up dir to store all manifests
@@ -111,6 +114,12 @@ pub async fn get_operator_catalog<T: DownloadImageInterface + Clone>(
         let local_manifestlist = manifestlist.clone();
         let local_pml = parse_json_manifestlist(local_manifestlist.clone())?;
         for m in local_pml.clone().manifests.iter() {
+            if m.platform.is_none() || m.digest.is_none() {
+                return Err(MirrorError::new(&format!(
+                    "[get_operator_catalog] manifest list entry without platform or digest for {}",
+                    operator.catalog
+                )));
+            }
             let arch = m.platform.as_ref().unwrap().architecture.to_string();
             let manifest_json = format!(
                 "{}/{}/{}/{}/manifest.json",
@@ -144,7 +153,8 @@ pub async fn get_operator_catalog<T: DownloadImageInterface + Clone>(
                 ir.version.clone(),
                 arch.clone(),
             );
-            let cache_exists = Path::new(&working_dir_cache).exists();
+            // an update interrupted between the two renames of swap_cache leaves no cache
+            recover_cache(log, &working_dir_cache)?;
             log.debug(&format!(
                 "[get_operator_catalog] main operator manifest file {}",
                 manifest_json
@@ -158,20 +168,61 @@ pub async fn get_operator_catalog<T: DownloadImageInterface + Clone>(
             .await?;
             if changed.is_some() {
                 log.info("[get_operator_catalog] detected change in manifest");
-                let changed_manifest = fs_handler(changed.unwrap().clone(), "read", None).await?;
-                let res_pm = parse_json_manifest_operator(changed_manifest.clone())?;
+                // new layers are extracted into a staging directory, the current
+                // cache is only replaced once the updated configs have been built
+                // every failure from here on discards the staging directory and the
+                // updated manifest, so that the next update retries
+                let working_dir_staging = format!("{}-staging", working_dir_cache);
+                let res_pm = fs_handler(changed.unwrap().clone(), "read", None)
+                    .await
+                    .and_then(parse_json_manifest_operator);
+                if res_pm.is_err() {
+                    discard_staging(log, &working_dir_staging, &manifest_json);
+                    return Err(catalog_error(
+                        &operator.catalog,
+                        &arch,
+                        "reading changed manifest",
+                        res_pm.err().unwrap().to_string(),
+                    ));
+                }
+                let res_pm = res_pm.unwrap();
+                if res_pm.layers.is_none() {
+                    discard_staging(log, &working_dir_staging, &manifest_json);
+                    return Err(catalog_error(
+                        &operator.catalog,
+                        &arch,
+                        "manifest has no layers",
+                        "".to_string(),
+                    ));
+                }
 
-                if cache_exists {
-                    // detected a change so clean the dir contents
-                    rm_rf::remove(&working_dir_cache)
-                        .expect("[get_operator_catalog] should delete current untarred cache");
+                if Path::new(&working_dir_staging).exists() {
+                    // leftover from a previous interrupted update
+                    let res = make_writable(working_dir_staging.clone())
+                        .map_err(|e| e.to_string())
+                        .and_then(|_| {
+                            rm_rf::remove(&working_dir_staging).map_err(|e| e.to_string())
+                        });
+                    if res.is_err() {
+                        discard_staging(log, &working_dir_staging, &manifest_json);
+                        return Err(catalog_error(
+                            &operator.catalog,
+                            &arch,
+                            "deleting stale staging directory",
+                            res.err().unwrap().to_string(),
+                        ));
+                    }
+                }
+                let res = create_staging(&working_dir_staging, &perms);
+                if res.is_err() {
+                    discard_staging(log, &working_dir_staging, &manifest_json);
+                    return Err(catalog_error(
+                        &operator.catalog,
+                        &arch,
+                        "creating staging directory",
+                        res.err().unwrap().to_string(),
+                    ));
                 }
-                // re-create the cache directory
-                let mut builder = DirBuilder::new();
-                builder.mode(0o777);
-                builder
-                    .create(&working_dir_cache)
-                    .expect("[get_operator_catalog] unable to create directory");
 
                 let mut fslayers: Vec<FsLayer> = vec![];
                 for l in res_pm.clone().layers.unwrap().iter() {
@@ -189,7 +240,17 @@ pub async fn get_operator_catalog<T: DownloadImageInterface + Clone>(
                 let mut hm: HashMap<String, Vec<FsLayer>> = HashMap::new();
                 hm.insert(blobs_url, fslayers.clone());
                 // use a concurrent process to get related blobs
-                execute_batch(reg_con.clone(), log, blobs_dir.clone(), false, true, hm).await?;
+                let res_batch =
+                    execute_batch(reg_con.clone(), log, blobs_dir.clone(), false, true, hm).await;
+                if res_batch.is_err() {
+                    discard_staging(log, &working_dir_staging, &manifest_json);
+                    return Err(catalog_error(
+                        &operator.catalog,
+                        &arch,
+                        "downloading blobs",
+                        res_batch.err().unwrap().to_string(),
+                    ));
+                }
                 log.debug(&format!(
                     "[get_operator_catalog] completed image index download"
                 ));
@@ -197,27 +258,54 @@ pub async fn get_operator_catalog<T: DownloadImageInterface + Clone>(
                     "[get_operator_catalog] map {:#?}",
                     fslayers.clone(),
                 ));
-                untar_layers(
+                let res_untar = untar_layers(
                     log,
                     blobs_dir.clone(),
-                    working_dir_cache.clone(),
+                    working_dir_staging.clone(),
                     fslayers.clone(),
                 )
                 .await;
+                if res_untar.is_err() {
+                    discard_staging(log, &working_dir_staging, &manifest_json);
+                    return Err(catalog_error(
+                        &operator.catalog,
+                        &arch,
+                        "untarring layers",
+                        res_untar.err().unwrap().to_string(),
+                    ));
+                }
 
                 log.hi("[get_operator_catalog] completed untar of layers");
                 // find the directory 'configs'
                 let config_dir =
-                    find_dir(log, working_dir_cache.clone(), "configs".to_string()).await;
+                    find_dir(log, working_dir_staging.clone(), "configs".to_string()).await;
                 if config_dir.len() == 0 {
-                    log.warn("[get_operator_catalog] 'configs' directory is empty");
+                    log.warn(&format!(
+                        "[get_operator_catalog] 'configs' directory is empty for {} ({}), keeping previous cache",
+                        operator.catalog, arch
+                    ));
+                    discard_staging(log, &working_dir_staging, &manifest_json);
                 } else {
                     log.mid(&format!(
                         "[get_operator_catalog] full path for directory 'configs' {} ",
                         &config_dir
                     ));
-                    DeclarativeConfig::build_updated_configs(log, config_dir.clone())
-                        .expect("[get_operator_catalog] should build updated configs");
+                    let res_install = install_staging(
+                        log,
+                        &config_dir,
+                        &working_dir_staging,
+                        &working_dir_cache,
+                        &perms,
+                    );
+                    if res_install.is_err() {
+                        discard_staging(log, &working_dir_staging, &manifest_json);
+                        return Err(catalog_error(
+                            &operator.catalog,
+                            &arch,
+                            "installing updated cache, keeping previous cache",
+                            res_install.err().unwrap().to_string(),
+                        ));
+                    }
                 }
             }
 
@@ -231,6 +319,131 @@ pub async fn get_operator_catalog<T: DownloadImageInterface + Clone>(
     Ok(())
 }
 
+// the staging directory stays owner writable while the layers are extracted, the
+// configured (possibly read-only) mode is applied by install_staging
+fn create_staging(staging: &str, perms: &CachePermissions) -> Result<(), std::io::Error> {
+    let mut builder = DirBuilder::new();
+    builder.mode(0o700 | perms.dir_mode());
+    builder.create(staging)
+}
+
+// build the updated configs in the staging directory and swap it in as the cache
+fn install_staging(
+    log: &Logging,
+    config_dir: &str,
+    staging: &str,
+    cache: &str,
+    perms: &CachePermissions,
+) -> Result<(), MirrorError> {
+    let res = DeclarativeConfig::build_updated_configs(log, config_dir.to_string());
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[install_staging] building updated configs {}",
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    apply_cache_permissions(log, staging.to_string(), perms)?;
+    swap_cache(log, staging, cache)
+}
+
+// replace the current cache with the fully built staging directory
+// this is two renames (cache -> <cache>-previous, staging -> cache) and not atomic,
+// if the process dies in between there is no cache until recover_cache restores
+// the previous one on the next update
+fn swap_cache(log: &Logging, staging: &str, cache: &str) -> Result<(), MirrorError> {
+    let previous = format!("{}-previous", cache);
+    if Path::new(&previous).exists() {
+        make_writable(previous.clone())?;
+        let res = rm_rf::remove(&previous);
+        if res.is_err() {
+            return Err(MirrorError::new(&format!(
+                "[swap_cache] deleting previous cache {} {}",
+                previous,
+                res.err().unwrap().to_string().to_lowercase()
+            )));
+        }
+    }
+    if Path::new(cache).exists() {
+        let res = fs::rename(cache, &previous);
+        if res.is_err() {
+            return Err(MirrorError::new(&format!(
+                "[swap_cache] moving current cache {} {}",
+                cache,
+                res.err().unwrap().to_string().to_lowercase()
+            )));
+        }
+    }
+    let res = fs::rename(staging, cache);
+    if res.is_err() {
+        // put the previous cache back in place
+        if Path::new(&previous).exists() {
+            let _ = fs::rename(&previous, cache);
+        }
+        return Err(MirrorError::new(&format!(
+            "[swap_cache] moving staging directory {} {}",
+            staging,
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    if Path::new(&previous).exists() {
+        // the new cache is in place, a leftover previous cache is removed on the next swap
+        if make_writable(previous.clone()).is_err() || rm_rf::remove(&previous).is_err() {
+            log.warn(&format!(
+                "[swap_cache] unable to delete previous cache {}",
+                previous
+            ));
+        }
+    }
+    log.debug(&format!("[swap_cache] cache {} updated", cache));
+    Ok(())
+}
+
+// restore <cache>-previous when the cache itself is missing (interrupted swap)
+fn recover_cache(log: &Logging, cache: &str) -> Result<(), MirrorError> {
+    let previous = format!("{}-previous", cache);
+    if Path::new(cache).exists() || !Path::new(&previous).exists() {
+        return Ok(());
+    }
+    log.warn(&format!(
+        "[recover_cache] restoring {} from an interrupted update",
+        cache
+    ));
+    let res = fs::rename(&previous, cache);
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[recover_cache] restoring previous cache {} {}",
+            previous,
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    Ok(())
+}
+
+// build an error for a failed catalog update, carrying the catalog reference and architecture
+fn catalog_error(catalog: &str, arch: &str, msg: &str, err: String) -> MirrorError {
+    let mut details = format!("[get_operator_catalog] {} for {} ({})", msg, catalog, arch);
+    if err.len() > 0 {
+        details = format!("{} {}", details, err.to_lowercase());
+    }
+    MirrorError::new(&details)
+}
+
+// remove a failed staging directory and the updated manifest, so that the
+// next refresh detects the change again while the previous cache stays in place
+fn discard_staging(log: &Logging, staging: &str, manifest_json: &str) {
+    log.warn(&format!(
+        "[discard_staging] update failed, keeping previous cache (removing {})",
+        staging
+    ));
+    if Path::new(staging).exists() {
+        let _ = make_writable(staging.to_string());
+        let _ = rm_rf::remove(staging);
+    }
+    if Path::new(manifest_json).exists() {
+        let _ = fs::remove_file(manifest_json);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     // this brings everything from parent's scope into this scope
@@ -238,6 +451,7 @@ mod tests {
     use async_trait::async_trait;
     use mirror_copy::Manifest;
     use std::fs;
+    use std::os::unix::fs::PermissionsExt;
 
     macro_rules! aw {
         ($e:expr) => {
@@ -245,6 +459,105 @@ mod tests {
         };
     }
 
+    #[test]
+    fn swap_cache_pass() {
+        let log = &Logging {
+            log_level: Level::DEBUG,
+        };
+        let dir = "./test-artifacts/swap-cache";
+        let cache = format!("{}/cache", dir);
+        let staging = format!("{}/cache-staging", dir);
+        let previous = format!("{}/cache-previous", dir);
+        let manifest_json = format!("{}/manifest.json", dir);
+        fs::create_dir_all(&cache).expect("should create cache");
+        fs::write(format!("{}/old", cache), "old").expect("should write old cache file");
+        fs::create_dir_all(&staging).expect("should create staging");
+        fs::write(format!("{}/new", staging), "new").expect("should write staging file");
+
+        // the staging directory replaces the cache, the previous cache is removed
+        swap_cache(log, &staging, &cache).expect("should swap cache");
+        assert!(Path::new(&format!("{}/new", cache)).exists());
+        assert!(!Path::new(&format!("{}/old", cache)).exists());
+        assert!(!Path::new(&staging).exists());
+        assert!(!Path::new(&previous).exists());
+
+        // an interrupted swap (no cache, only the previous one) is restored
+        fs::rename(&cache, &previous).expect("should simulate interrupted swap");
+        recover_cache(log, &cache).expect("should recover cache");
+        assert!(Path::new(&format!("{}/new", cache)).exists());
+        assert!(!Path::new(&previous).exists());
+        // nothing to do when the cache is in place
+        recover_cache(log, &cache).expect("should leave cache untouched");
+        assert!(Path::new(&cache).exists());
+
+        // a failed update removes the staging directory and the updated manifest
+        fs::create_dir_all(&staging).expect("should create staging");
+        fs::write(&manifest_json, "{}").expect("should write manifest");
+        discard_staging(log, &staging, &manifest_json);
+        assert!(!Path::new(&staging).exists());
+        assert!(!Path::new(&manifest_json).exists());
+        assert!(Path::new(&format!("{}/new", cache)).exists());
+
+        fs::remove_dir_all(dir).expect("should delete swap cache test folder");
+    }
+
+    #[test]
+    fn install_read_only_cache_pass() {
+        let log = &Logging {
+            log_level: Level::DEBUG,
+        };
+        let dir = "./test-artifacts/read-only-cache";
+        let cache = format!("{}/cache", dir);
+        let staging = format!("{}/cache-staging", dir);
+        let config_dir = format!("{}/layer/configs/test-operator", staging);
+        fs::create_dir_all(dir).expect("should create read only cache test folder");
+        let perms = CachePermissions {
+            mode: 0o755,
+            read_only: true,
+            umask: 0o022,
+            owner: None,
+        };
+
+        // the layers are extracted into a writable staging directory (as a non-root user a
+        // read-only staging directory fails with permission denied)
+        create_staging(&staging, &perms).expect("should create staging");
+        assert_eq!(
+            fs::metadata(&staging).unwrap().permissions().mode() & 0o700,
+            0o700
+        );
+        fs::create_dir_all(&config_dir).expect("should extract configs");
+        fs::write(format!("{}/catalog.json", config_dir), "{}").expect("should extract catalog");
+
+        // the read-only mode is applied once the configs are built and swapped in
+        install_staging(
+            log,
+            &format!("{}/layer/configs", staging),
+            &staging,
+            &cache,
+            &perms,
+        )
+        .expect("should install staging");
+        assert!(!Path::new(&staging).exists());
+        assert_eq!(
+            fs::metadata(&cache).unwrap().permissions().mode() & 0o777,
+            0o555
+        );
+        assert_eq!(
+            fs::metadata(format!(
+                "{}/layer/configs/test-operator/catalog.json",
+                cache
+            ))
+            .unwrap()
+            .permissions()
+            .mode()
+                & 0o777,
+            0o444
+        );
+
+        make_writable(cache.clone()).expect("should make cache writable");
+        fs::remove_dir_all(dir).expect("should delete read only cache test folder");
+    }
+
     #[test]
     fn get_operator_catalog_pass() {
         let log = &Logging {
@@ -353,7 +666,8 @@ mod tests {
             String::from("./test-artifacts/"),
             false,
             false,
-            ops.clone()
+            ops.clone(),
+            CachePermissions::default()
         ));
         println!("result -> {}", res.is_ok());
     }
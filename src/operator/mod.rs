@@ -1 +1,2 @@
 pub mod collector;
+pub mod permissions;
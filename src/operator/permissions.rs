@@ -0,0 +1,258 @@
+use custom_logger::*;
+use mirror_error::MirrorError;
+use std::fs;
+use std::os::unix::fs::{lchown, PermissionsExt};
+use walkdir::WalkDir;
+
+// default mode for the untarred cache directories (the process umask is applied on top)
+pub const DEFAULT_CACHE_MODE: u32 = 0o755;
+
+// umask used when it can't be read from /proc/self/status
+const FALLBACK_UMASK: u32 = 0o022;
+
+// permission settings applied to the untarred catalog cache
+#[derive(Debug, Clone)]
+pub struct CachePermissions {
+    pub mode: u32,
+    pub read_only: bool,
+    pub umask: u32,
+    // uid:gid applied to the extracted entries, ownership is left as extracted when not set
+    pub owner: Option<(u32, u32)>,
+}
+
+impl Default for CachePermissions {
+    fn default() -> Self {
+        CachePermissions {
+            mode: DEFAULT_CACHE_MODE,
+            read_only: false,
+            umask: process_umask(),
+            owner: None,
+        }
+    }
+}
+
+impl CachePermissions {
+    // parse an octal mode string (i.e "755" or "0o750") and an optional "uid:gid" owner
+    // world writable modes are rejected
+    pub fn new(mode: String, read_only: bool, owner: Option<String>) -> Result<Self, MirrorError> {
+        let trimmed = mode.trim_start_matches("0o");
+        let res = u32::from_str_radix(trimmed, 8);
+        if res.is_err() || res.as_ref().unwrap() > &0o777 {
+            return Err(MirrorError::new(&format!(
+                "[CachePermissions] invalid cache mode '{}' (expected octal i.e 755)",
+                mode
+            )));
+        }
+        let mode_bits = res.unwrap();
+        if mode_bits & 0o002 != 0 {
+            return Err(MirrorError::new(&format!(
+                "[CachePermissions] cache mode '{}' is world writable",
+                mode
+            )));
+        }
+        let owner = match owner {
+            Some(o) => Some(parse_owner(&o)?),
+            None => None,
+        };
+        Ok(CachePermissions {
+            mode: mode_bits,
+            read_only,
+            umask: process_umask(),
+            owner,
+        })
+    }
+
+    // the directory mode with the umask (and read-only) applied
+    pub fn dir_mode(&self) -> u32 {
+        let mode = self.mode & !self.umask;
+        if self.read_only {
+            return mode & 0o555;
+        }
+        mode
+    }
+
+    // the mode used for regular files, directory mode without execute bits
+    pub fn file_mode(&self) -> u32 {
+        self.dir_mode() & 0o666
+    }
+}
+
+// "uid:gid" (numeric) i.e 1001:1001
+fn parse_owner(owner: &str) -> Result<(u32, u32), MirrorError> {
+    let parts: Vec<&str> = owner.split(':').collect();
+    if parts.len() == 2 {
+        if let (Ok(uid), Ok(gid)) = (parts[0].parse::<u32>(), parts[1].parse::<u32>()) {
+            return Ok((uid, gid));
+        }
+    }
+    Err(MirrorError::new(&format!(
+        "[CachePermissions] invalid cache owner '{}' (expected numeric uid:gid i.e 1001:1001)",
+        owner
+    )))
+}
+
+// the umask of the current process, read without changing it (umask(2) always sets it)
+fn process_umask() -> u32 {
+    let status = fs::read_to_string("/proc/self/status").unwrap_or_default();
+    status
+        .lines()
+        .find(|l| l.starts_with("Umask:"))
+        .and_then(|l| u32::from_str_radix(l.trim_start_matches("Umask:").trim(), 8).ok())
+        .unwrap_or(FALLBACK_UMASK)
+}
+
+// normalize the permissions (and optionally the ownership) of everything extracted by untar_layers
+// directories get the configured mode, files the same mode without execute bits, both with
+// the umask applied, setuid/setgid/sticky bits coming from the layers are never kept
+pub fn apply_cache_permissions(
+    log: &Logging,
+    dir: String,
+    perms: &CachePermissions,
+) -> Result<(), MirrorError> {
+    let dir_mode = perms.dir_mode();
+    let file_mode = perms.file_mode();
+    log.debug(&format!(
+        "[apply_cache_permissions] {} directories {:o} files {:o} owner {:?}",
+        dir, dir_mode, file_mode, perms.owner
+    ));
+    // files first and directories last (contents_first), so a read-only
+    // directory never blocks updating its own entries
+    for entry in WalkDir::new(&dir).contents_first(true).into_iter() {
+        if entry.is_err() {
+            return Err(MirrorError::new(&format!(
+                "[apply_cache_permissions] reading {} {}",
+                dir,
+                entry.err().unwrap().to_string().to_lowercase()
+            )));
+        }
+        let e = entry.unwrap();
+        let path = e.path().display().to_string();
+        if let Some((uid, gid)) = perms.owner {
+            let res = lchown(e.path(), Some(uid), Some(gid));
+            if res.is_err() {
+                return Err(MirrorError::new(&format!(
+                    "[apply_cache_permissions] chown {} {}",
+                    path,
+                    res.err().unwrap().to_string().to_lowercase()
+                )));
+            }
+        }
+        if e.path_is_symlink() {
+            continue;
+        }
+        let mode = if e.file_type().is_dir() {
+            dir_mode
+        } else {
+            file_mode
+        };
+        set_mode(path, mode)?;
+    }
+    Ok(())
+}
+
+// restore owner write access on a (possibly read-only) cache so it can be removed
+pub fn make_writable(dir: String) -> Result<(), MirrorError> {
+    for entry in WalkDir::new(&dir).into_iter() {
+        if entry.is_err() {
+            continue;
+        }
+        let e = entry.unwrap();
+        if e.path_is_symlink() || !e.file_type().is_dir() {
+            continue;
+        }
+        let res = fs::metadata(e.path());
+        if res.is_ok() {
+            let mode = res.unwrap().permissions().mode();
+            set_mode(e.path().display().to_string(), mode | 0o700)?;
+        }
+    }
+    Ok(())
+}
+
+fn set_mode(path: String, mode: u32) -> Result<(), MirrorError> {
+    let res = fs::set_permissions(&path, fs::Permissions::from_mode(mode));
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[set_mode] {} {}",
+            path,
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+
+    fn mode_of(path: &str) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o7777
+    }
+
+    #[test]
+    fn cache_permissions_new_pass() {
+        let perms = CachePermissions::new("750".to_string(), false, None).unwrap();
+        assert_eq!(perms.mode, 0o750);
+        let perms = CachePermissions::new("0o755".to_string(), true, Some("1001:1002".to_string()))
+            .unwrap();
+        assert_eq!(perms.mode, 0o755);
+        assert!(perms.read_only);
+        assert_eq!(perms.owner, Some((1001, 1002)));
+
+        // not octal, out of range, world writable and invalid owner
+        assert!(CachePermissions::new("abc".to_string(), false, None).is_err());
+        assert!(CachePermissions::new("1755".to_string(), false, None).is_err());
+        assert!(CachePermissions::new("777".to_string(), false, None).is_err());
+        assert!(CachePermissions::new("755".to_string(), false, Some("root".to_string())).is_err());
+
+        // the umask is applied on top of the mode
+        let perms = CachePermissions {
+            mode: 0o775,
+            read_only: false,
+            umask: 0o027,
+            owner: None,
+        };
+        assert_eq!(perms.dir_mode(), 0o750);
+        assert_eq!(perms.file_mode(), 0o640);
+        let perms = CachePermissions {
+            read_only: true,
+            ..perms
+        };
+        assert_eq!(perms.dir_mode(), 0o550);
+        assert_eq!(perms.file_mode(), 0o440);
+    }
+
+    #[test]
+    fn apply_cache_permissions_pass() {
+        let log = &Logging {
+            log_level: Level::DEBUG,
+        };
+        let dir = "./test-artifacts/cache-permissions".to_string();
+        let sub = format!("{}/configs/test-operator", dir);
+        let file = format!("{}/catalog.json", sub);
+        fs::create_dir_all(&sub).expect("should create cache permissions test folder");
+        fs::write(&file, "{}").expect("should write catalog");
+        set_mode(file.clone(), 0o4777).expect("should set file mode");
+
+        // owner set to the current owner, so the test doesn't need root
+        let meta = fs::metadata(&dir).unwrap();
+        let perms = CachePermissions {
+            mode: 0o775,
+            read_only: true,
+            umask: 0o022,
+            owner: Some((meta.uid(), meta.gid())),
+        };
+        apply_cache_permissions(log, dir.clone(), &perms).expect("should apply permissions");
+        assert_eq!(mode_of(&dir), 0o555);
+        assert_eq!(mode_of(&sub), 0o555);
+        assert_eq!(mode_of(&file), 0o444);
+
+        // the read-only cache can be made writable again and removed
+        make_writable(dir.clone()).expect("should make cache writable");
+        assert_eq!(mode_of(&dir), 0o755);
+        assert_eq!(mode_of(&sub), 0o755);
+        fs::remove_dir_all(&dir).expect("should delete cache permissions test folder");
+    }
+}
@@ -0,0 +1,586 @@
+use crate::fbc::reader::*;
+use crate::registry::model;
+use crate::serve::api::*;
+use crate::upgradepath::dependencies::provides_gvk;
+use custom_logger::*;
+use mirror_error::MirrorError;
+use semver::Version;
+use serde_json::{json, Value};
+
+// the declarative config of a package, kept in memory while serving
+#[derive(Debug, Clone, Default)]
+pub struct RegistryPackage {
+    pub package: OlmPackage,
+    pub channels: Vec<OlmChannel>,
+    pub bundles: Vec<OlmBundle>,
+    pub deprecations: OlmDeprecations,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RegistryCatalog {
+    pub name: String,
+    pub packages: Vec<RegistryPackage>,
+}
+
+// read all packages of a cached catalog (name:version) in memory
+pub fn load_registry_catalog(
+    log: &Logging,
+    dir: String,
+    catalog: String,
+) -> Result<RegistryCatalog, MirrorError> {
+    let res = catalog_configs_dir(dir, &catalog);
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[load_registry_catalog] {}",
+            res.err().unwrap().message()
+        )));
+    }
+    let config_dir = res.unwrap();
+    let mut packages = vec![];
+    for name in list_packages(config_dir.clone()).iter() {
+        // a package that can't be read is left out instead of failing the whole catalog
+        let res = read_package_blobs(config_dir.clone(), name.clone());
+        if res.is_err() {
+            log.warn(&format!(
+                "[load_registry_catalog] skipping {} {}",
+                name,
+                res.err().unwrap().to_string().to_lowercase()
+            ));
+            continue;
+        }
+        let blobs = res.unwrap();
+        let package = get_package(&blobs);
+        if package.is_none() {
+            log.warn(&format!(
+                "[load_registry_catalog] no olm.package found for {}, skipping",
+                name
+            ));
+            continue;
+        }
+        packages.push(RegistryPackage {
+            package: package.unwrap(),
+            channels: get_channels(&blobs),
+            bundles: get_bundles(&blobs),
+            deprecations: get_deprecations(&blobs),
+        });
+    }
+    log.debug(&format!(
+        "[load_registry_catalog] {} packages loaded for {}",
+        packages.len(),
+        catalog
+    ));
+    Ok(RegistryCatalog {
+        name: catalog,
+        packages,
+    })
+}
+
+impl RegistryPackage {
+    fn channel(&self, name: &String) -> Result<&OlmChannel, ApiError> {
+        match self.channels.iter().find(|c| &c.name == name) {
+            Some(c) => Ok(c),
+            None => Err(ApiError::NotFound(format!(
+                "channel {} not found for package {}",
+                name, self.package.name
+            ))),
+        }
+    }
+
+    fn default_channel(&self) -> String {
+        self.package.default_channel.clone().unwrap_or_default()
+    }
+
+    // the api bundle of a channel entry
+    fn bundle(
+        &self,
+        channel: &OlmChannel,
+        entry: &OlmChannelEntry,
+    ) -> Result<model::Bundle, ApiError> {
+        match self.bundles.iter().find(|b| b.name == entry.name) {
+            Some(b) => Ok(to_bundle(self, channel, entry, b)),
+            None => Err(ApiError::NotFound(format!(
+                "bundle {} not found for package {}",
+                entry.name, self.package.name
+            ))),
+        }
+    }
+
+    // the bundle at the head of a channel
+    fn head_bundle(&self, channel: &OlmChannel) -> Result<model::Bundle, ApiError> {
+        let head = channel.head().unwrap_or_default();
+        match channel.entries.iter().find(|e| e.name == head) {
+            Some(e) => self.bundle(channel, e),
+            None => Err(ApiError::NotFound(format!(
+                "no head found for channel {} of package {}",
+                channel.name, self.package.name
+            ))),
+        }
+    }
+
+    fn provides(&self, entry: &OlmChannelEntry, gvk: &Value) -> bool {
+        self.bundles
+            .iter()
+            .any(|b| b.name == entry.name && provides_gvk(b, gvk))
+    }
+
+    // the semver of a channel entry bundle (from its olm.package property)
+    fn version(&self, entry: &OlmChannelEntry) -> Option<Version> {
+        self.bundles
+            .iter()
+            .find(|b| b.name == entry.name)
+            .and_then(|b| b.version())
+            .and_then(|v| Version::parse(&v).ok())
+    }
+}
+
+impl RegistryCatalog {
+    fn package(&self, name: &String) -> Result<&RegistryPackage, ApiError> {
+        match self.packages.iter().find(|p| &p.package.name == name) {
+            Some(p) => Ok(p),
+            None => Err(ApiError::NotFound(format!(
+                "package {} not found in catalog {}",
+                name, self.name
+            ))),
+        }
+    }
+
+    pub fn list_packages(&self) -> Vec<model::PackageName> {
+        self.packages
+            .iter()
+            .map(|p| model::PackageName {
+                name: p.package.name.clone(),
+            })
+            .collect()
+    }
+
+    pub fn get_package(&self, name: &String) -> Result<model::Package, ApiError> {
+        let pkg = self.package(name)?;
+        let mut channels: Vec<model::Channel> = pkg
+            .channels
+            .iter()
+            .map(|c| model::Channel {
+                name: c.name.clone(),
+                csv_name: c.head().unwrap_or_default(),
+                deprecation: deprecation(pkg.deprecations.channel(&c.name)),
+            })
+            .collect();
+        channels.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(model::Package {
+            name: pkg.package.name.clone(),
+            channels,
+            default_channel_name: pkg.default_channel(),
+            deprecation: deprecation(pkg.deprecations.package()),
+        })
+    }
+
+    pub fn get_bundle(
+        &self,
+        package: &String,
+        channel: &String,
+        csv_name: &String,
+    ) -> Result<model::Bundle, ApiError> {
+        let pkg = self.package(package)?;
+        let ch = pkg.channel(channel)?;
+        match ch.entries.iter().find(|e| &e.name == csv_name) {
+            Some(e) => pkg.bundle(ch, e),
+            None => Err(ApiError::NotFound(format!(
+                "bundle {} not found in channel {} of package {}",
+                csv_name, channel, package
+            ))),
+        }
+    }
+
+    pub fn get_bundle_for_channel(
+        &self,
+        package: &String,
+        channel: &String,
+    ) -> Result<model::Bundle, ApiError> {
+        let pkg = self.package(package)?;
+        pkg.head_bundle(pkg.channel(channel)?)
+    }
+
+    // every channel entry replacing (or skipping) the bundle
+    pub fn get_channel_entries_that_replace(&self, csv_name: &String) -> Vec<model::ChannelEntry> {
+        let mut entries = vec![];
+        for pkg in self.packages.iter() {
+            for ch in pkg.channels.iter() {
+                for e in ch.entries.iter() {
+                    let skips = e.skips.clone().unwrap_or(vec![]);
+                    if e.replaces.as_ref() == Some(csv_name) || skips.contains(csv_name) {
+                        entries.push(channel_entry(pkg, ch, &e.name, csv_name.clone()));
+                    }
+                }
+            }
+        }
+        entries
+    }
+
+    pub fn get_bundle_that_replaces(
+        &self,
+        csv_name: &String,
+        package: &String,
+        channel: &String,
+    ) -> Result<model::Bundle, ApiError> {
+        let pkg = self.package(package)?;
+        let ch = pkg.channel(channel)?;
+        let entry = ch.entries.iter().find(|e| {
+            e.replaces.as_ref() == Some(csv_name)
+                || e.skips.clone().unwrap_or(vec![]).contains(csv_name)
+        });
+        match entry {
+            Some(e) => pkg.bundle(ch, e),
+            None => Err(ApiError::NotFound(format!(
+                "no bundle replaces {} in channel {} of package {}",
+                csv_name, channel, package
+            ))),
+        }
+    }
+
+    // every channel entry whose bundle provides the api, latest only keeps the
+    // highest version providing it in each channel
+    pub fn get_channel_entries_that_provide(
+        &self,
+        request: &model::GetAllProvidersRequest,
+        latest: bool,
+    ) -> Vec<model::ChannelEntry> {
+        let gvk = gvk_value(request);
+        let mut entries = vec![];
+        for pkg in self.packages.iter() {
+            for ch in pkg.channels.iter() {
+                let mut providers: Vec<&OlmChannelEntry> = ch
+                    .entries
+                    .iter()
+                    .filter(|e| pkg.provides(e, &gvk))
+                    .collect();
+                if latest {
+                    providers = providers
+                        .into_iter()
+                        .max_by_key(|e| pkg.version(e))
+                        .into_iter()
+                        .collect();
+                }
+                for e in providers.iter() {
+                    let replaces = e.replaces.clone().unwrap_or_default();
+                    entries.push(channel_entry(pkg, ch, &e.name, replaces));
+                }
+            }
+        }
+        entries
+    }
+
+    // the head of the default channel of the first package providing the api
+    pub fn get_default_bundle_that_provides(
+        &self,
+        request: &model::GetAllProvidersRequest,
+    ) -> Result<model::Bundle, ApiError> {
+        let gvk = gvk_value(request);
+        for pkg in self.packages.iter() {
+            let ch = pkg.channel(&pkg.default_channel());
+            if ch.is_err() {
+                continue;
+            }
+            let bundle = pkg.head_bundle(ch.unwrap());
+            if bundle.is_ok()
+                && pkg
+                    .bundles
+                    .iter()
+                    .any(|b| b.name == bundle.as_ref().unwrap().csv_name && provides_gvk(b, &gvk))
+            {
+                return bundle;
+            }
+        }
+        Err(ApiError::NotFound(format!(
+            "no default bundle provides {}/{} {}",
+            request.group, request.version, request.kind
+        )))
+    }
+
+    // a bundle is listed once per channel it belongs to
+    pub fn list_bundles(&self) -> Vec<model::Bundle> {
+        let mut bundles = vec![];
+        for pkg in self.packages.iter() {
+            for ch in pkg.channels.iter() {
+                for e in ch.entries.iter() {
+                    if let Ok(b) = pkg.bundle(ch, e) {
+                        bundles.push(b);
+                    }
+                }
+            }
+        }
+        bundles
+    }
+}
+
+fn deprecation(message: Option<String>) -> Option<model::Deprecation> {
+    message.map(|m| model::Deprecation { message: m })
+}
+
+fn channel_entry(
+    pkg: &RegistryPackage,
+    channel: &OlmChannel,
+    name: &String,
+    replaces: String,
+) -> model::ChannelEntry {
+    model::ChannelEntry {
+        package_name: pkg.package.name.clone(),
+        channel_name: channel.name.clone(),
+        bundle_name: name.clone(),
+        replaces,
+    }
+}
+
+fn gvk_value(request: &model::GetAllProvidersRequest) -> Value {
+    json!({ "group": request.group, "version": request.version, "kind": request.kind })
+}
+
+fn to_gvk(value: &Value) -> model::GroupVersionKind {
+    let field = |k: &str| value[k].as_str().unwrap_or_default().to_string();
+    model::GroupVersionKind {
+        group: field("group"),
+        version: field("version"),
+        kind: field("kind"),
+        plural: String::new(),
+    }
+}
+
+// convert an olm.bundle (and its channel entry) to the registry api bundle
+fn to_bundle(
+    pkg: &RegistryPackage,
+    channel: &OlmChannel,
+    entry: &OlmChannelEntry,
+    bundle: &OlmBundle,
+) -> model::Bundle {
+    // olm.bundle.object properties hold the base64 encoded bundle manifests
+    let mut objects = bundle.objects();
+    let mut csv_json = objects
+        .iter()
+        .find(|o| {
+            serde_json::from_str::<Value>(o)
+                .map(|v| v["kind"] == "ClusterServiceVersion")
+                .unwrap_or(false)
+        })
+        .cloned()
+        .unwrap_or_default();
+    // newer catalogs only ship the olm.csv.metadata property, build a minimal csv from it
+    if csv_json.len() == 0 {
+        if let Some(metadata) = bundle.csv_metadata() {
+            let csv = json!({
+                "apiVersion": "operators.coreos.com/v1alpha1",
+                "kind": "ClusterServiceVersion",
+                "metadata": {
+                    "name": bundle.name,
+                    "annotations": metadata["annotations"],
+                    "labels": metadata["labels"],
+                },
+                "spec": metadata,
+            });
+            csv_json = csv.to_string();
+            objects.push(csv_json.clone());
+        }
+    }
+    let mut dependencies = vec![];
+    for v in bundle.get_properties("olm.gvk.required").iter() {
+        dependencies.push(model::Dependency {
+            r#type: "olm.gvk".to_string(),
+            value: v.to_string(),
+        });
+    }
+    // olm reads the package dependency range from 'version' (versionRange in the declarative config)
+    for v in bundle.get_properties("olm.package.required").iter() {
+        dependencies.push(model::Dependency {
+            r#type: "olm.package".to_string(),
+            value: json!({ "packageName": v["packageName"], "version": v["versionRange"] })
+                .to_string(),
+        });
+    }
+    model::Bundle {
+        csv_name: bundle.name.clone(),
+        package_name: pkg.package.name.clone(),
+        channel_name: channel.name.clone(),
+        csv_json,
+        object: objects,
+        bundle_path: bundle.image.clone().unwrap_or_default(),
+        provided_apis: bundle
+            .get_properties("olm.gvk")
+            .iter()
+            .map(to_gvk)
+            .collect(),
+        required_apis: bundle
+            .get_properties("olm.gvk.required")
+            .iter()
+            .map(to_gvk)
+            .collect(),
+        version: bundle.version().unwrap_or_default(),
+        skip_range: entry.skip_range.clone().unwrap_or_default(),
+        dependencies,
+        properties: bundle
+            .properties
+            .clone()
+            .unwrap_or(vec![])
+            .iter()
+            .map(|p| model::Property {
+                r#type: p.property_type.clone(),
+                value: p.value.to_string(),
+            })
+            .collect(),
+        replaces: entry.replaces.clone().unwrap_or_default(),
+        skips: entry.skips.clone().unwrap_or(vec![]),
+        deprecation: deprecation(pkg.deprecations.bundle(&bundle.name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn registry_catalog_pass() {
+        let blobs: Vec<Value> = serde_json::from_str(
+            "[
+            { \"schema\": \"olm.package\", \"name\": \"test-operator\", \"defaultChannel\": \"stable\" },
+            { \"schema\": \"olm.channel\", \"name\": \"stable\", \"package\": \"test-operator\",
+              \"entries\": [
+                { \"name\": \"test-operator.v0.0.1\" },
+                { \"name\": \"test-operator.v0.0.2\", \"replaces\": \"test-operator.v0.0.1\", \"skipRange\": \"<0.0.2\" } ] },
+            { \"schema\": \"olm.channel\", \"name\": \"candidate\", \"package\": \"test-operator\",
+              \"entries\": [
+                { \"name\": \"test-operator.v0.0.1\" },
+                { \"name\": \"test-operator.v0.0.3\", \"replaces\": \"test-operator.v0.0.1\" } ] },
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.1\", \"package\": \"test-operator\", \"image\": \"quay.io/test/bundle@sha256:01\",
+              \"properties\": [
+                { \"type\": \"olm.package\", \"value\": { \"packageName\": \"test-operator\", \"version\": \"0.0.1\" } },
+                { \"type\": \"olm.gvk\", \"value\": { \"group\": \"test.io\", \"version\": \"v1\", \"kind\": \"Test\" } } ] },
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.2\", \"package\": \"test-operator\", \"image\": \"quay.io/test/bundle@sha256:02\",
+              \"properties\": [
+                { \"type\": \"olm.package\", \"value\": { \"packageName\": \"test-operator\", \"version\": \"0.0.2\" } },
+                { \"type\": \"olm.gvk\", \"value\": { \"group\": \"test.io\", \"version\": \"v1\", \"kind\": \"Test\" } },
+                { \"type\": \"olm.package.required\", \"value\": { \"packageName\": \"other-operator\", \"versionRange\": \">=1.0.0\" } },
+                { \"type\": \"olm.csv.metadata\", \"value\": { \"displayName\": \"Test Operator\" } } ] },
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.3\", \"package\": \"test-operator\", \"image\": \"quay.io/test/bundle@sha256:03\",
+              \"properties\": [ { \"type\": \"olm.package\", \"value\": { \"packageName\": \"test-operator\", \"version\": \"0.0.3\" } } ] },
+            { \"schema\": \"olm.deprecations\", \"package\": \"test-operator\",
+              \"entries\": [ { \"reference\": { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.1\" }, \"message\": \"use v0.0.2\" } ] }
+            ]",
+        )
+        .expect("should parse blobs");
+        let catalog = RegistryCatalog {
+            name: "test-index:v0.0.1".to_string(),
+            packages: vec![RegistryPackage {
+                package: get_package(&blobs).unwrap(),
+                channels: get_channels(&blobs),
+                bundles: get_bundles(&blobs),
+                deprecations: get_deprecations(&blobs),
+            }],
+        };
+        let pkg_name = "test-operator".to_string();
+        let stable = "stable".to_string();
+
+        assert_eq!(catalog.list_packages()[0].name, pkg_name);
+        let pkg = catalog.get_package(&pkg_name).expect("should get package");
+        assert_eq!(pkg.default_channel_name, "stable");
+        let channel = pkg.channels.iter().find(|c| c.name == stable).unwrap();
+        assert_eq!(channel.csv_name, "test-operator.v0.0.2");
+        assert_eq!(
+            catalog
+                .get_package(&"other".to_string())
+                .err()
+                .unwrap()
+                .status(),
+            404
+        );
+
+        let head = catalog
+            .get_bundle_for_channel(&pkg_name, &stable)
+            .expect("should get channel head");
+        assert_eq!(head.csv_name, "test-operator.v0.0.2");
+        assert_eq!(head.replaces, "test-operator.v0.0.1");
+        assert_eq!(head.skip_range, "<0.0.2");
+        assert_eq!(head.version, "0.0.2");
+        assert_eq!(head.provided_apis[0].kind, "Test");
+        assert!(head.csv_json.contains("\"displayName\":\"Test Operator\""));
+        let dependency: Value = serde_json::from_str(&head.dependencies[0].value).unwrap();
+        assert_eq!(head.dependencies[0].r#type, "olm.package");
+        assert_eq!(
+            dependency,
+            json!({ "packageName": "other-operator", "version": ">=1.0.0" })
+        );
+
+        let bundle = catalog
+            .get_bundle(&pkg_name, &stable, &"test-operator.v0.0.1".to_string())
+            .expect("should get bundle");
+        assert_eq!(bundle.bundle_path, "quay.io/test/bundle@sha256:01");
+        assert_eq!(bundle.deprecation.unwrap().message, "use v0.0.2");
+
+        let entries = catalog.get_channel_entries_that_replace(&"test-operator.v0.0.1".to_string());
+        // one per channel
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].bundle_name, "test-operator.v0.0.2");
+        assert_eq!(entries[1].bundle_name, "test-operator.v0.0.3");
+        let replacing = catalog
+            .get_bundle_that_replaces(&"test-operator.v0.0.1".to_string(), &pkg_name, &stable)
+            .expect("should get replacing bundle");
+        assert_eq!(replacing.csv_name, "test-operator.v0.0.2");
+
+        let gvk = model::GetAllProvidersRequest {
+            group: "test.io".to_string(),
+            version: "v1".to_string(),
+            kind: "Test".to_string(),
+            plural: String::new(),
+        };
+        assert_eq!(
+            catalog.get_channel_entries_that_provide(&gvk, false).len(),
+            3
+        );
+        // the latest provider of each channel, not only the channel heads
+        let latest: Vec<(String, String)> = catalog
+            .get_channel_entries_that_provide(&gvk, true)
+            .iter()
+            .map(|e| (e.channel_name.clone(), e.bundle_name.clone()))
+            .collect();
+        assert_eq!(
+            latest,
+            vec![
+                (stable.clone(), "test-operator.v0.0.2".to_string()),
+                ("candidate".to_string(), "test-operator.v0.0.1".to_string())
+            ]
+        );
+        let provider = catalog
+            .get_default_bundle_that_provides(&gvk)
+            .expect("should get default provider");
+        assert_eq!(provider.csv_name, "test-operator.v0.0.2");
+        assert_eq!(catalog.list_bundles().len(), 4);
+    }
+
+    #[test]
+    fn load_registry_catalog_pass() {
+        let log = &Logging {
+            log_level: Level::INFO,
+        };
+        let dir = "./test-artifacts/registry".to_string();
+        let config_dir = format!("{}/test-index/v0.0.1/amd64/cache/layer/configs", dir);
+        fs::create_dir_all(format!("{}/test-operator", config_dir))
+            .expect("should create registry test folder");
+        fs::create_dir_all(format!("{}/broken-operator", config_dir))
+            .expect("should create registry test folder");
+        fs::write(
+            format!("{}/test-operator/catalog.json", config_dir),
+            "{ \"schema\": \"olm.package\", \"name\": \"test-operator\", \"defaultChannel\": \"stable\" }",
+        )
+        .expect("should write catalog");
+        fs::write(
+            format!("{}/broken-operator/catalog.json", config_dir),
+            "{ \"schema\": \"olm.package\", ",
+        )
+        .expect("should write broken catalog");
+
+        // the broken package is skipped
+        let catalog = load_registry_catalog(log, dir.clone(), "test-index:v0.0.1".to_string())
+            .expect("should load catalog");
+        assert_eq!(catalog.packages.len(), 1);
+        assert_eq!(catalog.packages[0].package.name, "test-operator");
+
+        fs::remove_dir_all(&dir).expect("should delete registry test folder");
+    }
+}
@@ -0,0 +1,3 @@
+pub mod catalog;
+pub mod model;
+pub mod server;
@@ -0,0 +1,173 @@
+// messages of the OLM operator-registry api package (pkg/api/registry.proto)
+// the field tags must match the upstream proto, clients are generated from it
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Deprecation {
+    #[prost(string, tag = "1")]
+    pub message: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Channel {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub csv_name: String,
+    #[prost(message, optional, tag = "3")]
+    pub deprecation: Option<Deprecation>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PackageName {
+    #[prost(string, tag = "1")]
+    pub name: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Package {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(message, repeated, tag = "2")]
+    pub channels: Vec<Channel>,
+    #[prost(string, tag = "3")]
+    pub default_channel_name: String,
+    #[prost(message, optional, tag = "4")]
+    pub deprecation: Option<Deprecation>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GroupVersionKind {
+    #[prost(string, tag = "1")]
+    pub group: String,
+    #[prost(string, tag = "2")]
+    pub version: String,
+    #[prost(string, tag = "3")]
+    pub kind: String,
+    #[prost(string, tag = "4")]
+    pub plural: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Dependency {
+    #[prost(string, tag = "1")]
+    pub r#type: String,
+    #[prost(string, tag = "2")]
+    pub value: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Property {
+    #[prost(string, tag = "1")]
+    pub r#type: String,
+    #[prost(string, tag = "2")]
+    pub value: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Bundle {
+    #[prost(string, tag = "1")]
+    pub csv_name: String,
+    #[prost(string, tag = "2")]
+    pub package_name: String,
+    #[prost(string, tag = "3")]
+    pub channel_name: String,
+    #[prost(string, tag = "4")]
+    pub csv_json: String,
+    #[prost(string, repeated, tag = "5")]
+    pub object: Vec<String>,
+    #[prost(string, tag = "6")]
+    pub bundle_path: String,
+    #[prost(message, repeated, tag = "7")]
+    pub provided_apis: Vec<GroupVersionKind>,
+    #[prost(message, repeated, tag = "8")]
+    pub required_apis: Vec<GroupVersionKind>,
+    #[prost(string, tag = "9")]
+    pub version: String,
+    #[prost(string, tag = "10")]
+    pub skip_range: String,
+    #[prost(message, repeated, tag = "11")]
+    pub dependencies: Vec<Dependency>,
+    #[prost(message, repeated, tag = "12")]
+    pub properties: Vec<Property>,
+    #[prost(string, tag = "13")]
+    pub replaces: String,
+    #[prost(string, repeated, tag = "14")]
+    pub skips: Vec<String>,
+    #[prost(message, optional, tag = "15")]
+    pub deprecation: Option<Deprecation>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ChannelEntry {
+    #[prost(string, tag = "1")]
+    pub package_name: String,
+    #[prost(string, tag = "2")]
+    pub channel_name: String,
+    #[prost(string, tag = "3")]
+    pub bundle_name: String,
+    #[prost(string, tag = "4")]
+    pub replaces: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListPackageRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListBundlesRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetPackageRequest {
+    #[prost(string, tag = "1")]
+    pub name: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetBundleRequest {
+    #[prost(string, tag = "1")]
+    pub pkg_name: String,
+    #[prost(string, tag = "2")]
+    pub channel_name: String,
+    #[prost(string, tag = "3")]
+    pub csv_name: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetBundleInChannelRequest {
+    #[prost(string, tag = "1")]
+    pub pkg_name: String,
+    #[prost(string, tag = "2")]
+    pub channel_name: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetAllReplacementsRequest {
+    #[prost(string, tag = "1")]
+    pub csv_name: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetReplacementRequest {
+    #[prost(string, tag = "1")]
+    pub csv_name: String,
+    #[prost(string, tag = "2")]
+    pub pkg_name: String,
+    #[prost(string, tag = "3")]
+    pub channel_name: String,
+}
+
+// GetAllProvidersRequest, GetLatestProvidersRequest and GetDefaultProviderRequest
+// share the same fields
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetAllProvidersRequest {
+    #[prost(string, tag = "1")]
+    pub group: String,
+    #[prost(string, tag = "2")]
+    pub version: String,
+    #[prost(string, tag = "3")]
+    pub kind: String,
+    #[prost(string, tag = "4")]
+    pub plural: String,
+}
+
+pub type GetLatestProvidersRequest = GetAllProvidersRequest;
+pub type GetDefaultProviderRequest = GetAllProvidersRequest;
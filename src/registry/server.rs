@@ -0,0 +1,186 @@
+use crate::registry::catalog::*;
+use crate::registry::model::*;
+use crate::serve::api::ApiError;
+use custom_logger::*;
+use mirror_error::MirrorError;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+// api.Registry service stubs generated by build.rs
+pub mod api {
+    include!(concat!(env!("OUT_DIR"), "/api.Registry.rs"));
+}
+
+use api::registry_server::{Registry, RegistryServer};
+
+// server streams are sent from the in-memory results
+type ResultStream<T> = futures::stream::Iter<std::vec::IntoIter<Result<T, Status>>>;
+
+fn stream<T>(items: Vec<T>) -> ResultStream<T> {
+    futures::stream::iter(items.into_iter().map(Ok).collect::<Vec<_>>())
+}
+
+impl From<ApiError> for Status {
+    fn from(err: ApiError) -> Self {
+        match err {
+            ApiError::NotFound(m) => Status::not_found(m),
+            ApiError::BadRequest(m) => Status::invalid_argument(m),
+            ApiError::Unavailable(m) => Status::unavailable(m),
+            ApiError::Internal(m) => Status::internal(m),
+        }
+    }
+}
+
+pub struct RegistryService {
+    pub catalog: Arc<RegistryCatalog>,
+}
+
+#[tonic::async_trait]
+impl Registry for RegistryService {
+    type ListPackagesStream = ResultStream<PackageName>;
+    type GetChannelEntriesThatReplaceStream = ResultStream<ChannelEntry>;
+    type GetChannelEntriesThatProvideStream = ResultStream<ChannelEntry>;
+    type GetLatestChannelEntriesThatProvideStream = ResultStream<ChannelEntry>;
+    type ListBundlesStream = ResultStream<Bundle>;
+
+    async fn list_packages(
+        &self,
+        _request: Request<ListPackageRequest>,
+    ) -> Result<Response<Self::ListPackagesStream>, Status> {
+        Ok(Response::new(stream(self.catalog.list_packages())))
+    }
+
+    async fn get_package(
+        &self,
+        request: Request<GetPackageRequest>,
+    ) -> Result<Response<Package>, Status> {
+        let req = request.into_inner();
+        Ok(Response::new(self.catalog.get_package(&req.name)?))
+    }
+
+    async fn get_bundle(
+        &self,
+        request: Request<GetBundleRequest>,
+    ) -> Result<Response<Bundle>, Status> {
+        let req = request.into_inner();
+        Ok(Response::new(self.catalog.get_bundle(
+            &req.pkg_name,
+            &req.channel_name,
+            &req.csv_name,
+        )?))
+    }
+
+    async fn get_bundle_for_channel(
+        &self,
+        request: Request<GetBundleInChannelRequest>,
+    ) -> Result<Response<Bundle>, Status> {
+        let req = request.into_inner();
+        Ok(Response::new(self.catalog.get_bundle_for_channel(
+            &req.pkg_name,
+            &req.channel_name,
+        )?))
+    }
+
+    async fn get_channel_entries_that_replace(
+        &self,
+        request: Request<GetAllReplacementsRequest>,
+    ) -> Result<Response<Self::GetChannelEntriesThatReplaceStream>, Status> {
+        let req = request.into_inner();
+        Ok(Response::new(stream(
+            self.catalog.get_channel_entries_that_replace(&req.csv_name),
+        )))
+    }
+
+    async fn get_bundle_that_replaces(
+        &self,
+        request: Request<GetReplacementRequest>,
+    ) -> Result<Response<Bundle>, Status> {
+        let req = request.into_inner();
+        Ok(Response::new(self.catalog.get_bundle_that_replaces(
+            &req.csv_name,
+            &req.pkg_name,
+            &req.channel_name,
+        )?))
+    }
+
+    async fn get_channel_entries_that_provide(
+        &self,
+        request: Request<GetAllProvidersRequest>,
+    ) -> Result<Response<Self::GetChannelEntriesThatProvideStream>, Status> {
+        let req = request.into_inner();
+        Ok(Response::new(stream(
+            self.catalog.get_channel_entries_that_provide(&req, false),
+        )))
+    }
+
+    async fn get_latest_channel_entries_that_provide(
+        &self,
+        request: Request<GetLatestProvidersRequest>,
+    ) -> Result<Response<Self::GetLatestChannelEntriesThatProvideStream>, Status> {
+        let req = request.into_inner();
+        Ok(Response::new(stream(
+            self.catalog.get_channel_entries_that_provide(&req, true),
+        )))
+    }
+
+    async fn get_default_bundle_that_provides(
+        &self,
+        request: Request<GetDefaultProviderRequest>,
+    ) -> Result<Response<Bundle>, Status> {
+        let req = request.into_inner();
+        Ok(Response::new(
+            self.catalog.get_default_bundle_that_provides(&req)?,
+        ))
+    }
+
+    async fn list_bundles(
+        &self,
+        _request: Request<ListBundlesRequest>,
+    ) -> Result<Response<Self::ListBundlesStream>, Status> {
+        Ok(Response::new(stream(self.catalog.list_bundles())))
+    }
+}
+
+// serve the catalog over the api.Registry grpc service (and grpc.health.v1 for probes)
+pub async fn run_registry(
+    log: &Logging,
+    catalog: RegistryCatalog,
+    address: String,
+) -> Result<(), MirrorError> {
+    let res = address.parse::<SocketAddr>();
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[run_registry] address {} {}",
+            address,
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    let addr = res.unwrap();
+    let (mut health_reporter, health_service) = tonic_health::server::health_reporter();
+    health_reporter
+        .set_serving::<RegistryServer<RegistryService>>()
+        .await;
+    log.info(&format!(
+        "[run_registry] serving {} ({} packages) on {}",
+        catalog.name,
+        catalog.packages.len(),
+        address
+    ));
+    let service = RegistryService {
+        catalog: Arc::new(catalog),
+    };
+    let res = Server::builder()
+        .add_service(health_service)
+        .add_service(RegistryServer::new(service))
+        .serve(addr)
+        .await;
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[run_registry] {}",
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,328 @@
+use crate::fbc::reader::*;
+use crate::isc::generate::*;
+use crate::upgradepath::dependencies::{selected_bundles, selected_head};
+use custom_logger::*;
+use mirror_error::MirrorError;
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+
+// a deduplicated image referenced by one or more bundles
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImageRef {
+    #[serde(rename = "image")]
+    pub image: String,
+
+    // none when the image is referenced by tag (not resolved to a digest)
+    #[serde(rename = "digest")]
+    pub digest: Option<String>,
+
+    #[serde(rename = "resolved")]
+    pub resolved: bool,
+
+    #[serde(rename = "names")]
+    pub names: Vec<String>,
+
+    #[serde(rename = "bundles")]
+    pub bundles: Vec<String>,
+}
+
+// collect the related images (and bundle images) of every bundle selected by the generated isc
+// (the bundles, the min/max versions of the package or its channels)
+pub async fn get_related_images(
+    log: &Logging,
+    dir: String,
+    isc: &IscConfig,
+) -> Result<Vec<ImageRef>, MirrorError> {
+    let mut images: Vec<ImageRef> = vec![];
+    for ctlog in isc.operators.iter() {
+        let config_dir = get_configs_dir(log, dir.clone(), ctlog.catalog.clone()).await?;
+        for pkg in ctlog.packages.iter() {
+            let blobs = read_package_blobs(config_dir.clone(), pkg.name.clone())?;
+            let (bundles, errors) = parse_bundles(&blobs);
+            for e in errors.iter() {
+                log.warn(&format!(
+                    "[get_related_images] invalid bundle in package {} ({}) {}",
+                    pkg.name, ctlog.catalog, e
+                ));
+            }
+            for b in pkg.bundles.iter() {
+                if !bundles.iter().any(|x| x.name == b.name) {
+                    log.warn(&format!(
+                        "[get_related_images] bundle {} not found in package {} ({})",
+                        b.name, pkg.name, ctlog.catalog
+                    ));
+                }
+            }
+            let selected = selected_bundles(pkg, &bundles, selected_head(pkg, &blobs));
+            if selected.len() == 0 {
+                log.warn(&format!(
+                    "[get_related_images] no bundles selected for package {} ({})",
+                    pkg.name, ctlog.catalog
+                ));
+            }
+            for bundle in selected.iter() {
+                if bundle.image.is_some() {
+                    add_image(
+                        &mut images,
+                        bundle.image.clone().unwrap(),
+                        None,
+                        bundle.name.clone(),
+                    );
+                }
+                if bundle.related_images.is_some() {
+                    for ri in bundle.related_images.as_ref().unwrap().iter() {
+                        add_image(
+                            &mut images,
+                            ri.image.clone(),
+                            ri.name.clone(),
+                            bundle.name.clone(),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    images.sort_by(|a, b| a.image.cmp(&b.image));
+    Ok(images)
+}
+
+// display the related images and save them (text and json) to the output directory
+pub async fn process_related_images(
+    log: &Logging,
+    dir: String,
+    output_dir: String,
+    isc: &IscConfig,
+) -> Result<(), MirrorError> {
+    let images = get_related_images(log, dir, isc).await?;
+    log.hi(&format!("related images ({})", images.len()));
+    let mut text = String::from("");
+    for i in images.iter().filter(|i| i.resolved) {
+        log.lo(&format!("  {}", i.image));
+        text += &format!("{}\n", i.image);
+    }
+    // images referenced by tag are listed separately, their digest is not known
+    let unresolved: Vec<&ImageRef> = images.iter().filter(|i| !i.resolved).collect();
+    if unresolved.len() > 0 {
+        log.warn(&format!(
+            "[process_related_images] {} image(s) referenced by tag, not resolved to a digest",
+            unresolved.len()
+        ));
+        text += "# unresolved (referenced by tag, no digest)\n";
+        for i in unresolved.iter() {
+            log.warn(&format!("  {}", i.image));
+            text += &format!("{}\n", i.image);
+        }
+    }
+    let json = serde_json::to_string_pretty(&images).unwrap();
+    for (file, contents) in [
+        (format!("{}/related-images.txt", output_dir), text),
+        (format!("{}/related-images.json", output_dir), json),
+    ] {
+        let res = fs::write(&file, contents);
+        if res.is_err() {
+            return Err(MirrorError::new(&format!(
+                "[process_related_images] writing {} {}",
+                file,
+                res.err().unwrap().to_string().to_lowercase()
+            )));
+        }
+        log.info(&format!("[process_related_images] saved {}", file));
+    }
+    Ok(())
+}
+
+// add (or merge) an image into the deduplicated list
+fn add_image(images: &mut Vec<ImageRef>, image: String, name: Option<String>, bundle: String) {
+    let pos = images.iter().position(|x| x.image == image);
+    let index = match pos {
+        Some(i) => i,
+        None => {
+            let digest = match image.split_once('@') {
+                Some((_, d)) => Some(d.to_string()),
+                None => None,
+            };
+            images.push(ImageRef {
+                image: image.clone(),
+                resolved: digest.is_some(),
+                digest,
+                names: vec![],
+                bundles: vec![],
+            });
+            images.len() - 1
+        }
+    };
+    let img = &mut images[index];
+    if name.is_some() && !img.names.contains(name.as_ref().unwrap()) {
+        img.names.push(name.unwrap());
+    }
+    if !img.bundles.contains(&bundle) {
+        img.bundles.push(bundle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn add_image_pass() {
+        let mut images = vec![];
+        let digest_ref = "quay.io/test/operator@sha256:01".to_string();
+        add_image(
+            &mut images,
+            digest_ref.clone(),
+            Some("operator".to_string()),
+            "test-operator.v0.0.1".to_string(),
+        );
+        add_image(
+            &mut images,
+            digest_ref.clone(),
+            Some("manager".to_string()),
+            "test-operator.v0.0.2".to_string(),
+        );
+        add_image(
+            &mut images,
+            digest_ref.clone(),
+            Some("operator".to_string()),
+            "test-operator.v0.0.2".to_string(),
+        );
+        add_image(
+            &mut images,
+            "quay.io/test/proxy:v1".to_string(),
+            None,
+            "test-operator.v0.0.2".to_string(),
+        );
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].digest, Some("sha256:01".to_string()));
+        assert!(images[0].resolved);
+        assert_eq!(images[0].names, vec!["operator", "manager"]);
+        assert_eq!(
+            images[0].bundles,
+            vec!["test-operator.v0.0.1", "test-operator.v0.0.2"]
+        );
+        assert_eq!(images[1].digest, None);
+        assert!(!images[1].resolved);
+    }
+
+    #[test]
+    fn get_related_images_pass() {
+        let log = &Logging {
+            log_level: Level::DEBUG,
+        };
+        let dir = "./test-artifacts/related".to_string();
+        let config_dir = format!(
+            "{}/test-index/v0.0.1/amd64/cache/layer/configs/test-operator",
+            dir
+        );
+        fs::create_dir_all(&config_dir).expect("should create related test folder");
+        fs::write(
+            format!("{}/catalog.json", config_dir),
+            "{ \"schema\": \"olm.package\", \"name\": \"test-operator\", \"defaultChannel\": \"stable\" }
+            { \"schema\": \"olm.channel\", \"name\": \"stable\", \"package\": \"test-operator\",
+              \"entries\": [
+                { \"name\": \"test-operator.v0.0.1\" },
+                { \"name\": \"test-operator.v0.0.2\", \"replaces\": \"test-operator.v0.0.1\" } ] }
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.1\", \"package\": \"test-operator\",
+              \"image\": \"quay.io/test/bundle@sha256:01\",
+              \"properties\": [ { \"type\": \"olm.package\", \"value\": { \"packageName\": \"test-operator\", \"version\": \"0.0.1\" } } ],
+              \"relatedImages\": [
+                { \"name\": \"operator\", \"image\": \"quay.io/test/operator@sha256:02\" },
+                { \"name\": \"proxy\", \"image\": \"quay.io/test/proxy:v1\" } ] }
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.2\", \"package\": \"test-operator\",
+              \"image\": \"quay.io/test/bundle@sha256:03\",
+              \"properties\": [ { \"type\": \"olm.package\", \"value\": { \"packageName\": \"test-operator\", \"version\": \"0.0.2\" } } ],
+              \"relatedImages\": [ { \"name\": \"operator\", \"image\": \"quay.io/test/operator@sha256:02\" } ] }
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.3\", \"package\": 3 }",
+        )
+        .expect("should write catalog");
+        let mut isc = IscConfig::new("v2alpha1".to_string());
+        isc.operators = vec![Catalog {
+            catalog: "quay.io/test/test-index:v0.0.1".to_string(),
+            packages: vec![Package {
+                name: "test-operator".to_string(),
+                bundles: vec![
+                    Bundle {
+                        name: "test-operator.v0.0.1".to_string(),
+                    },
+                    Bundle {
+                        name: "test-operator.v0.0.2".to_string(),
+                    },
+                ],
+                ..Default::default()
+            }],
+        }];
+        let images = aw!(get_related_images(log, dir.clone(), &isc)).expect("should get images");
+        let names: Vec<&str> = images.iter().map(|i| i.image.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "quay.io/test/bundle@sha256:01",
+                "quay.io/test/bundle@sha256:03",
+                "quay.io/test/operator@sha256:02",
+                "quay.io/test/proxy:v1"
+            ]
+        );
+        assert_eq!(images[2].bundles.len(), 2);
+        assert!(!images[3].resolved);
+
+        // the channels and versions styles select the bundles by version range
+        let styles = [
+            Package {
+                name: "test-operator".to_string(),
+                channels: vec![IscChannel {
+                    name: "stable".to_string(),
+                    min_version: Some("0.0.2".to_string()),
+                    max_version: Some("0.0.2".to_string()),
+                }],
+                ..Default::default()
+            },
+            Package {
+                name: "test-operator".to_string(),
+                min_version: Some("0.0.2".to_string()),
+                max_version: Some("0.0.2".to_string()),
+                ..Default::default()
+            },
+            // no selection mirrors the default channel head
+            Package {
+                name: "test-operator".to_string(),
+                ..Default::default()
+            },
+        ];
+        for pkg in styles.into_iter() {
+            let mut styled = isc.clone();
+            styled.operators[0].packages = vec![pkg];
+            let images =
+                aw!(get_related_images(log, dir.clone(), &styled)).expect("should get images");
+            let names: Vec<&str> = images.iter().map(|i| i.image.as_str()).collect();
+            assert_eq!(
+                names,
+                vec![
+                    "quay.io/test/bundle@sha256:03",
+                    "quay.io/test/operator@sha256:02"
+                ]
+            );
+        }
+
+        let output_dir = format!("{}/output", dir);
+        fs::create_dir_all(&output_dir).expect("should create output folder");
+        aw!(process_related_images(
+            log,
+            dir.clone(),
+            output_dir.clone(),
+            &isc
+        ))
+        .expect("should write related images");
+        let text = fs::read_to_string(format!("{}/related-images.txt", output_dir)).unwrap();
+        assert!(
+            text.ends_with("# unresolved (referenced by tag, no digest)\nquay.io/test/proxy:v1\n")
+        );
+        fs::remove_dir_all(&dir).expect("should delete related test folder");
+    }
+}
@@ -0,0 +1,250 @@
+use crate::fbc::reader::*;
+use custom_logger::*;
+use mirror_catalog_index::find_dir;
+use mirror_error::MirrorError;
+use serde_json::Value;
+
+// a package matching the search query
+#[derive(Debug, Clone, Default)]
+pub struct SearchResult {
+    pub catalog: String,
+    pub package: String,
+    pub default_channel: String,
+    pub head_version: String,
+    pub matched_on: Vec<String>,
+}
+
+// search package names, display names, descriptions, keywords and provider
+// across every cached catalog in the working directory
+pub async fn search_catalogs(
+    log: &Logging,
+    dir: String,
+    query: String,
+) -> Result<Vec<SearchResult>, MirrorError> {
+    let q = query.to_lowercase();
+    let catalogs = list_cached_catalogs(dir.clone());
+    if catalogs.len() == 0 {
+        return Err(MirrorError::new(&format!(
+            "[search_catalogs] no cached catalogs found in {} (use the update sub command)",
+            dir
+        )));
+    }
+    let mut results: Vec<SearchResult> = vec![];
+    for (catalog, cache_dir) in catalogs.iter() {
+        log.debug(&format!("[search_catalogs] searching catalog {}", catalog));
+        let config_dir = find_dir(log, cache_dir.clone(), "configs".to_string()).await;
+        if config_dir.len() == 0 {
+            log.warn(&format!(
+                "[search_catalogs] no 'configs' directory found for {}",
+                catalog
+            ));
+            continue;
+        }
+        for name in list_packages(config_dir.clone()).iter() {
+            // an unreadable package doesn't stop the search
+            let res = read_package_blobs(config_dir.clone(), name.clone());
+            if res.is_err() {
+                log.warn(&format!(
+                    "[search_catalogs] skipping package {} in {} {}",
+                    name,
+                    catalog,
+                    res.err().unwrap().to_string().to_lowercase()
+                ));
+                continue;
+            }
+            let blobs = res.unwrap();
+            let pkg = get_package(&blobs).unwrap_or_default();
+            let default_channel = pkg.default_channel.clone().unwrap_or_default();
+            let channels = get_channels(&blobs);
+            let head = channels
+                .iter()
+                .find(|c| c.name == default_channel)
+                .and_then(|c| c.head())
+                .unwrap_or_default();
+            let bundles = get_bundles(&blobs);
+            let head_bundle = bundles.iter().find(|b| b.name == head);
+            let metadata = head_bundle
+                .and_then(|b| b.csv_metadata())
+                .unwrap_or(Value::Null);
+
+            let mut matched_on: Vec<String> = vec![];
+            if name.to_lowercase().contains(&q) {
+                matched_on.push("name".to_string());
+            }
+            if contains(&metadata["displayName"], &q) {
+                matched_on.push("displayName".to_string());
+            }
+            if contains(&metadata["description"], &q)
+                || pkg
+                    .description
+                    .clone()
+                    .unwrap_or_default()
+                    .to_lowercase()
+                    .contains(&q)
+            {
+                matched_on.push("description".to_string());
+            }
+            if metadata["keywords"].is_array()
+                && metadata["keywords"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .any(|k| contains(k, &q))
+            {
+                matched_on.push("keywords".to_string());
+            }
+            if contains(&metadata["provider"]["name"], &q) {
+                matched_on.push("provider".to_string());
+            }
+            if matched_on.len() > 0 {
+                let head_version = match head_bundle.and_then(|b| b.version()) {
+                    Some(v) => v,
+                    None => head.clone(),
+                };
+                results.push(SearchResult {
+                    catalog: catalog.clone(),
+                    package: name.clone(),
+                    default_channel,
+                    head_version,
+                    matched_on,
+                });
+            }
+        }
+    }
+    Ok(results)
+}
+
+// display the search results
+pub fn render_search(results: &Vec<SearchResult>) {
+    println!("");
+    for line in search_lines(results).iter() {
+        println!("{}", line);
+    }
+    println!("");
+}
+
+// the header and one line per search result
+fn search_lines(results: &Vec<SearchResult>) -> Vec<String> {
+    let mut lines = vec![
+        format!(
+            "\x1b[05C\x1b[1;97m{:<45}  {:<35}  {:<25}  {:<25}  {}\x1b[0m",
+            "PACKAGE", "CATALOG", "DEFAULT CHANNEL", "HEAD VERSION", "MATCHED ON"
+        ),
+        "\x1b[05C\x1b[1;97m---------------------------------------------  -----------------------------------  -------------------------  -------------------------  ------------------\x1b[0m".to_string(),
+    ];
+    for r in results.iter() {
+        lines.push(format!(
+            "\x1b[05C\x1b[0;97m{:<45}  {:<35}  \x1b[1;94m{:<25}\x1b[0;97m  {:<25}  {}\x1b[0m",
+            r.package,
+            r.catalog,
+            r.default_channel,
+            r.head_version,
+            r.matched_on.join(",")
+        ));
+    }
+    lines
+}
+
+fn contains(value: &Value, query: &str) -> bool {
+    value.is_string() && value.as_str().unwrap().to_lowercase().contains(query)
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+    use base64::{engine::general_purpose, Engine as _};
+    use std::fs;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn search_catalogs_pass() {
+        let log = &Logging {
+            log_level: Level::DEBUG,
+        };
+        let dir = "./test-artifacts/search".to_string();
+        let config_dir = format!("{}/test-index/v0.0.1/amd64/cache/layer/configs", dir);
+        for pkg in ["jaeger-product", "legacy-operator", "broken-operator"] {
+            fs::create_dir_all(format!("{}/{}", config_dir, pkg)).expect("should create package");
+        }
+        // csv metadata from the olm.csv.metadata property
+        fs::write(
+            format!("{}/jaeger-product/catalog.json", config_dir),
+            "{ \"schema\": \"olm.package\", \"name\": \"jaeger-product\", \"defaultChannel\": \"stable\" }
+            { \"schema\": \"olm.channel\", \"name\": \"stable\", \"package\": \"jaeger-product\",
+              \"entries\": [ { \"name\": \"jaeger-operator.v1.51.0-1\" } ] }
+            { \"schema\": \"olm.bundle\", \"name\": \"jaeger-operator.v1.51.0-1\", \"package\": \"jaeger-product\",
+              \"properties\": [
+                { \"type\": \"olm.package\", \"value\": { \"packageName\": \"jaeger-product\", \"version\": \"1.51.0-1\" } },
+                { \"type\": \"olm.csv.metadata\", \"value\": { \"displayName\": \"Red Hat OpenShift distributed tracing platform\",
+                  \"keywords\": [ \"tracing\" ], \"provider\": { \"name\": \"Red Hat\" } } } ] }",
+        )
+        .expect("should write catalog");
+        // older catalogs only have the csv in the olm.bundle.object properties
+        let csv = general_purpose::STANDARD.encode(
+            "{ \"kind\": \"ClusterServiceVersion\", \"metadata\": { \"name\": \"legacy-operator.v0.1.0\" },
+              \"spec\": { \"displayName\": \"Legacy\", \"description\": \"collects tracing spans\",
+              \"provider\": { \"name\": \"Community\" } } }",
+        );
+        fs::write(
+            format!("{}/legacy-operator/catalog.json", config_dir),
+            format!(
+                "{{ \"schema\": \"olm.package\", \"name\": \"legacy-operator\", \"defaultChannel\": \"alpha\" }}
+                {{ \"schema\": \"olm.channel\", \"name\": \"alpha\", \"package\": \"legacy-operator\",
+                  \"entries\": [ {{ \"name\": \"legacy-operator.v0.1.0\" }} ] }}
+                {{ \"schema\": \"olm.bundle\", \"name\": \"legacy-operator.v0.1.0\", \"package\": \"legacy-operator\",
+                  \"properties\": [ {{ \"type\": \"olm.bundle.object\", \"value\": {{ \"data\": \"{}\" }} }} ] }}",
+                csv
+            ),
+        )
+        .expect("should write catalog");
+        // an unparsable package is skipped
+        fs::write(
+            format!("{}/broken-operator/catalog.json", config_dir),
+            "{ tracing",
+        )
+        .expect("should write catalog");
+
+        let results = aw!(search_catalogs(log, dir.clone(), "Tracing".to_string()))
+            .expect("should search catalogs");
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].catalog, "test-index:v0.0.1");
+        assert_eq!(results[0].package, "jaeger-product");
+        assert_eq!(results[0].default_channel, "stable");
+        assert_eq!(results[0].head_version, "1.51.0-1");
+        assert_eq!(results[0].matched_on, vec!["displayName", "keywords"]);
+        assert_eq!(results[1].package, "legacy-operator");
+        assert_eq!(results[1].head_version, "legacy-operator.v0.1.0");
+        assert_eq!(results[1].matched_on, vec!["description"]);
+
+        let results = aw!(search_catalogs(log, dir.clone(), "community".to_string()))
+            .expect("should search catalogs");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].matched_on, vec!["provider"]);
+
+        let lines = search_lines(&results);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].contains("PACKAGE"), true);
+        assert_eq!(lines[2].contains("legacy-operator"), true);
+        assert_eq!(lines[2].contains("test-index:v0.0.1"), true);
+        assert_eq!(lines[2].contains("provider"), true);
+        render_search(&results);
+
+        // no cached catalogs
+        assert_eq!(
+            aw!(search_catalogs(
+                log,
+                "./test-artifacts/none".to_string(),
+                "x".to_string()
+            ))
+            .is_err(),
+            true
+        );
+        fs::remove_dir_all(&dir).expect("should delete search test folder");
+    }
+}
@@ -0,0 +1,306 @@
+use crate::api::schema::FilterOperator;
+use crate::fbc::reader::*;
+use crate::upgradepath::calculate::*;
+use custom_logger::*;
+use semver::Version;
+use serde_derive::Serialize;
+
+// errors returned by the api, mapped to http status codes by the server
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiError {
+    NotFound(String),
+    BadRequest(String),
+    Unavailable(String),
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn status(&self) -> u16 {
+        match self {
+            ApiError::NotFound(_) => 404,
+            ApiError::BadRequest(_) => 400,
+            ApiError::Unavailable(_) => 503,
+            ApiError::Internal(_) => 500,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ApiError::NotFound(m)
+            | ApiError::BadRequest(m)
+            | ApiError::Unavailable(m)
+            | ApiError::Internal(m) => m.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CatalogSummary {
+    pub name: String,
+    pub packages: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageDetails {
+    pub name: String,
+    pub default_channel: String,
+    pub description: Option<String>,
+    pub deprecated: Option<String>,
+    pub channels: Vec<ChannelDetails>,
+    pub bundles: Vec<BundleDetails>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ChannelDetails {
+    pub name: String,
+    pub head: Option<String>,
+    pub deprecated: Option<String>,
+    pub entries: Vec<OlmChannelEntry>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct BundleDetails {
+    pub name: String,
+    pub version: Option<String>,
+    pub image: Option<String>,
+    pub deprecated: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UpgradePath {
+    pub catalog: String,
+    pub package: String,
+    pub channel: String,
+    pub from_version: String,
+    pub path: Vec<String>,
+}
+
+// the cached catalogs (name:version) with their package count
+pub fn get_catalogs(dir: String) -> Vec<CatalogSummary> {
+    list_cached_catalogs(dir)
+        .iter()
+        .map(|(name, cache_dir)| CatalogSummary {
+            name: name.clone(),
+            packages: find_configs_dir(cache_dir)
+                .map(|d| list_packages(d).len())
+                .unwrap_or(0),
+        })
+        .collect()
+}
+
+pub fn get_packages(dir: String, catalog: String) -> Result<Vec<String>, ApiError> {
+    Ok(list_packages(catalog_configs_dir(dir, &catalog)?))
+}
+
+// channels (entries, head) and bundles of a package, with their deprecations
+pub fn get_package_details(
+    dir: String,
+    catalog: String,
+    package: String,
+) -> Result<PackageDetails, ApiError> {
+    let blobs = package_blobs(dir, &catalog, &package)?;
+    let pkg = get_package(&blobs).unwrap_or_default();
+    let deprecations = get_deprecations(&blobs);
+    let mut channels: Vec<ChannelDetails> = get_channels(&blobs)
+        .iter()
+        .map(|c| ChannelDetails {
+            name: c.name.clone(),
+            head: c.head(),
+            deprecated: deprecations.channel(&c.name),
+            entries: c.entries.clone(),
+        })
+        .collect();
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut bundles: Vec<BundleDetails> = get_bundles(&blobs)
+        .iter()
+        .map(|b| BundleDetails {
+            name: b.name.clone(),
+            version: b.version(),
+            image: b.image.clone(),
+            deprecated: deprecations.bundle(&b.name),
+        })
+        .collect();
+    bundles.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(PackageDetails {
+        name: package,
+        default_channel: pkg.default_channel.unwrap_or_default(),
+        description: pkg.description,
+        deprecated: deprecations.package(),
+        channels,
+        bundles,
+    })
+}
+
+// the upgrade path of a channel (defaults to the package default channel) from a version
+// calculated by list_channel_info, as for the upgradepath command
+pub fn get_upgrade_path(
+    log: &Logging,
+    dir: String,
+    catalog: String,
+    package: String,
+    channel: Option<String>,
+    from_version: Option<String>,
+) -> Result<UpgradePath, ApiError> {
+    let from_version = from_version.unwrap_or("0.0.0".to_string());
+    if Version::parse(&from_version).is_err() {
+        return Err(ApiError::BadRequest(format!(
+            "fromVersion {} is not a valid semver",
+            from_version
+        )));
+    }
+    let blobs = package_blobs(dir, &catalog, &package)?;
+    let channel = match channel {
+        Some(c) => c,
+        None => get_package(&blobs)
+            .and_then(|p| p.default_channel)
+            .unwrap_or_default(),
+    };
+    if !get_channels(&blobs).iter().any(|c| c.name == channel) {
+        return Err(ApiError::NotFound(format!(
+            "channel {} not found for package {}",
+            channel, package
+        )));
+    }
+    // the channels style selects the requested channel
+    let filter = FilterOperator {
+        name: package.clone(),
+        channel: Some(channel.clone()),
+        from_version: Some(from_version.clone()),
+        catalogs: None,
+    };
+    let res = list_channel_info(
+        log,
+        declarativeconfig_map(&blobs),
+        filter,
+        "channels".to_string(),
+        &get_deprecations(&blobs),
+        false,
+    );
+    if res.is_err() {
+        return Err(ApiError::Internal(res.err().unwrap().to_string()));
+    }
+    Ok(UpgradePath {
+        catalog,
+        package,
+        path: res.unwrap().path,
+        channel,
+        from_version,
+    })
+}
+
+pub fn catalog_configs_dir(dir: String, catalog: &String) -> Result<String, ApiError> {
+    let cached = list_cached_catalogs(dir);
+    let found = cached.iter().find(|(name, _)| name == catalog);
+    if found.is_none() {
+        return Err(ApiError::NotFound(format!("catalog {} not found", catalog)));
+    }
+    match find_configs_dir(&found.unwrap().1) {
+        Some(d) => Ok(d),
+        None => Err(ApiError::NotFound(format!(
+            "no 'configs' directory found for catalog {}",
+            catalog
+        ))),
+    }
+}
+
+fn package_blobs(
+    dir: String,
+    catalog: &String,
+    package: &String,
+) -> Result<Vec<serde_json::Value>, ApiError> {
+    let config_dir = catalog_configs_dir(dir, catalog)?;
+    if !list_packages(config_dir.clone()).contains(package) {
+        return Err(ApiError::NotFound(format!(
+            "package {} not found in catalog {}",
+            package, catalog
+        )));
+    }
+    read_package_blobs(config_dir, package.clone()).map_err(|e| ApiError::Internal(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn serve_api_pass() {
+        let dir = "./test-artifacts/serve".to_string();
+        let config_dir = format!(
+            "{}/test-index/v0.0.1/amd64/cache/layer/configs/test-operator",
+            dir
+        );
+        fs::create_dir_all(&config_dir).expect("should create serve test folder");
+        fs::write(
+            format!("{}/catalog.json", config_dir),
+            "{ \"schema\": \"olm.package\", \"name\": \"test-operator\", \"defaultChannel\": \"stable\" }
+            { \"schema\": \"olm.channel\", \"name\": \"stable\", \"package\": \"test-operator\",
+              \"entries\": [
+                { \"name\": \"test-operator.v0.0.1\" },
+                { \"name\": \"test-operator.v0.0.2\", \"replaces\": \"test-operator.v0.0.1\" } ] }
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.1\", \"package\": \"test-operator\", \"image\": \"quay.io/test/bundle@sha256:01\" }
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.2\", \"package\": \"test-operator\", \"image\": \"quay.io/test/bundle@sha256:02\" }",
+        )
+        .expect("should write catalog");
+        let catalog = "test-index:v0.0.1".to_string();
+
+        let catalogs = get_catalogs(dir.clone());
+        assert_eq!(
+            catalogs,
+            vec![CatalogSummary {
+                name: catalog.clone(),
+                packages: 1
+            }]
+        );
+        assert_eq!(
+            get_packages(dir.clone(), catalog.clone()).unwrap(),
+            vec!["test-operator"]
+        );
+        assert_eq!(
+            get_packages(dir.clone(), "other-index:v0.0.1".to_string())
+                .err()
+                .unwrap()
+                .status(),
+            404
+        );
+
+        let details =
+            get_package_details(dir.clone(), catalog.clone(), "test-operator".to_string())
+                .expect("should get package details");
+        assert_eq!(details.default_channel, "stable");
+        assert_eq!(
+            details.channels[0].head,
+            Some("test-operator.v0.0.2".to_string())
+        );
+        assert_eq!(details.bundles.len(), 2);
+
+        let log = &Logging {
+            log_level: Level::INFO,
+        };
+        let path = get_upgrade_path(
+            log,
+            dir.clone(),
+            catalog.clone(),
+            "test-operator".to_string(),
+            None,
+            Some("0.0.1".to_string()),
+        )
+        .expect("should get upgrade path");
+        assert_eq!(path.channel, "stable");
+        assert_eq!(path.path, vec!["test-operator.v0.0.2"]);
+        let res = get_upgrade_path(
+            log,
+            dir.clone(),
+            catalog.clone(),
+            "test-operator".to_string(),
+            None,
+            Some("latest".to_string()),
+        );
+        assert_eq!(res.err().unwrap().status(), 400);
+
+        fs::remove_dir_all(&dir).expect("should delete serve test folder");
+    }
+}
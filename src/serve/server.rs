@@ -0,0 +1,207 @@
+use crate::lock::cache::*;
+use crate::serve::api::*;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use custom_logger::*;
+use mirror_error::MirrorError;
+use serde_derive::Deserialize;
+use std::sync::Arc;
+
+pub struct ServeState {
+    pub log: Logging,
+    pub dir: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpgradePathQuery {
+    pub channel: Option<String>,
+    #[serde(rename = "fromVersion")]
+    pub from_version: Option<String>,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status()).unwrap();
+        let body = serde_json::json!({ "error": self.message() });
+        (status, Json(body)).into_response()
+    }
+}
+
+// read-only routes over the working-dir
+pub fn router(state: Arc<ServeState>) -> Router {
+    Router::new()
+        .route("/api/v1/catalogs", get(catalogs))
+        .route("/api/v1/catalogs/:catalog/packages", get(packages))
+        .route("/api/v1/catalogs/:catalog/packages/:package", get(package))
+        .route(
+            "/api/v1/catalogs/:catalog/packages/:package/upgradepath",
+            get(upgradepath),
+        )
+        .with_state(state)
+}
+
+pub async fn run_serve(log: Logging, dir: String, address: String) -> Result<(), MirrorError> {
+    let listener = tokio::net::TcpListener::bind(&address).await;
+    if listener.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[run_serve] binding {} {}",
+            address,
+            listener.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    log.info(&format!(
+        "[run_serve] serving {} on http://{}",
+        dir, address
+    ));
+    let state = Arc::new(ServeState { log, dir });
+    let res = axum::serve(listener.unwrap(), router(state)).await;
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[run_serve] {}",
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    Ok(())
+}
+
+// each request holds a shared lock, an update in progress returns 503
+fn read_lock(state: &ServeState) -> Result<CacheLock, ApiError> {
+    acquire_lock(&state.log, state.dir.clone(), false, false)
+        .map_err(|e| ApiError::Unavailable(e.to_string()))
+}
+
+// the handlers read the cache from disk, run them on the blocking thread pool
+async fn blocking<T, F>(state: Arc<ServeState>, f: F) -> Result<Json<T>, ApiError>
+where
+    T: Send + 'static,
+    F: FnOnce(&ServeState) -> Result<T, ApiError> + Send + 'static,
+{
+    let res = tokio::task::spawn_blocking(move || {
+        let _lock = read_lock(&state)?;
+        f(&state)
+    })
+    .await;
+    match res {
+        Ok(r) => r.map(Json),
+        Err(e) => Err(ApiError::Internal(format!(
+            "[blocking] {}",
+            e.to_string().to_lowercase()
+        ))),
+    }
+}
+
+async fn catalogs(State(state): State<Arc<ServeState>>) -> Result<impl IntoResponse, ApiError> {
+    blocking(state, |s| Ok(get_catalogs(s.dir.clone()))).await
+}
+
+async fn packages(
+    State(state): State<Arc<ServeState>>,
+    Path(catalog): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    blocking(state, move |s| get_packages(s.dir.clone(), catalog)).await
+}
+
+async fn package(
+    State(state): State<Arc<ServeState>>,
+    Path((catalog, package)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    blocking(state, move |s| {
+        get_package_details(s.dir.clone(), catalog, package)
+    })
+    .await
+}
+
+async fn upgradepath(
+    State(state): State<Arc<ServeState>>,
+    Path((catalog, package)): Path<(String, String)>,
+    Query(query): Query<UpgradePathQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    state.log.debug(&format!(
+        "[upgradepath] {} {} {:?}",
+        catalog, package, query
+    ));
+    blocking(state, move |s| {
+        get_upgrade_path(
+            &s.log,
+            s.dir.clone(),
+            catalog,
+            package,
+            query.channel,
+            query.from_version,
+        )
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use std::fs;
+    use tower::ServiceExt;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    fn get(app: Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let res = aw!(app.oneshot(req)).unwrap();
+        let status = res.status();
+        let body = aw!(to_bytes(res.into_body(), usize::MAX)).unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[test]
+    fn router_pass() {
+        let dir = "./test-artifacts/serve-router".to_string();
+        let config_dir = format!(
+            "{}/test-index/v0.0.1/amd64/cache/layer/configs/test-operator",
+            dir
+        );
+        fs::create_dir_all(&config_dir).expect("should create serve router test folder");
+        fs::write(
+            format!("{}/catalog.json", config_dir),
+            "{ \"schema\": \"olm.package\", \"name\": \"test-operator\", \"defaultChannel\": \"stable\" }
+            { \"schema\": \"olm.channel\", \"name\": \"stable\", \"package\": \"test-operator\",
+              \"entries\": [
+                { \"name\": \"test-operator.v0.0.1\" },
+                { \"name\": \"test-operator.v0.0.2\", \"replaces\": \"test-operator.v0.0.1\" } ] }",
+        )
+        .expect("should write catalog");
+        let state = Arc::new(ServeState {
+            log: Logging {
+                log_level: Level::INFO,
+            },
+            dir: dir.clone(),
+        });
+
+        let (status, body) = get(
+            router(state.clone()),
+            "/api/v1/catalogs/test-index:v0.0.1/packages",
+        );
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, serde_json::json!(["test-operator"]));
+        let (status, body) = get(
+            router(state.clone()),
+            "/api/v1/catalogs/test-index:v0.0.1/packages/test-operator/upgradepath?fromVersion=0.0.1",
+        );
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["path"], serde_json::json!(["test-operator.v0.0.2"]));
+        let (status, body) = get(
+            router(state.clone()),
+            "/api/v1/catalogs/other-index:v0.0.1/packages",
+        );
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "catalog other-index:v0.0.1 not found");
+
+        fs::remove_dir_all(&dir).expect("should delete serve router test folder");
+    }
+}
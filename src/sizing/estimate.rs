@@ -0,0 +1,450 @@
+use crate::fbc::reader::*;
+use crate::isc::generate::*;
+use crate::upgradepath::dependencies::{selected_bundles, selected_head};
+use custom_logger::*;
+use mirror_auth::{get_token, ImplTokenInterface};
+use mirror_copy::DownloadImageInterface;
+use mirror_error::MirrorError;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+// a registry image split into its api parts
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageParts {
+    pub registry: String,
+    pub repository: String,
+    pub reference: String,
+}
+
+// split an image (tag or digest) i.e registry.redhat.io/ns/name@sha256:abc
+pub fn split_image(image: String) -> Result<ImageParts, MirrorError> {
+    let (location, reference) = match image.split_once('@') {
+        Some((l, d)) => (l.to_string(), d.to_string()),
+        None => {
+            let last = image.rfind('/').unwrap_or(0);
+            match image[last..].rfind(':') {
+                Some(i) => (
+                    image[..last + i].to_string(),
+                    image[last + i + 1..].to_string(),
+                ),
+                None => (image.clone(), "latest".to_string()),
+            }
+        }
+    };
+    let res = location.split_once('/');
+    if res.is_none() {
+        return Err(MirrorError::new(&format!(
+            "[split_image] unable to parse image {}",
+            image
+        )));
+    }
+    let (registry, repository) = res.unwrap();
+    Ok(ImageParts {
+        registry: registry.to_string(),
+        repository: repository.to_string(),
+        reference,
+    })
+}
+
+// the estimated sizes, images that could not be resolved are listed as unknown
+// and not counted in the totals
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SizeReport {
+    // (catalog, package, size)
+    pub packages: Vec<(String, String, i64)>,
+    // (catalog, size)
+    pub catalogs: Vec<(String, i64)>,
+    pub total: i64,
+    pub unknown: Vec<String>,
+}
+
+// get the (digest, size) of all layers and the config blob of an image
+// manifest lists are resolved to the linux/amd64 manifest, images without one are rejected
+pub async fn get_image_layers<T: DownloadImageInterface + Clone>(
+    reg_con: T,
+    log: &Logging,
+    parts: &ImageParts,
+    token: String,
+) -> Result<Vec<(String, i64)>, MirrorError> {
+    let url = format!(
+        "https://{}/v2/{}/manifests/{}",
+        parts.registry, parts.repository, parts.reference
+    );
+    log.trace(&format!("[get_image_layers] api call manifest {}", url));
+    let mut manifest = parse_manifest(reg_con.get_manifest(url, token.clone()).await?)?;
+    if manifest["manifests"].is_array() {
+        let entries = manifest["manifests"].as_array().unwrap().clone();
+        let amd64 = entries
+            .iter()
+            .find(|m| m["platform"]["architecture"] == "amd64");
+        if amd64.is_none() || !amd64.unwrap()["digest"].is_string() {
+            return Err(MirrorError::new(&format!(
+                "[get_image_layers] no linux/amd64 manifest for {}/{}",
+                parts.registry, parts.repository
+            )));
+        }
+        let url = format!(
+            "https://{}/v2/{}/manifests/{}",
+            parts.registry,
+            parts.repository,
+            amd64.unwrap()["digest"].as_str().unwrap()
+        );
+        manifest = parse_manifest(reg_con.get_manifest(url, token).await?)?;
+    }
+    let mut layers = vec![];
+    if manifest["config"]["digest"].is_string() {
+        layers.push((
+            manifest["config"]["digest"].as_str().unwrap().to_string(),
+            manifest["config"]["size"].as_i64().unwrap_or(0),
+        ));
+    }
+    if manifest["layers"].is_array() {
+        for l in manifest["layers"].as_array().unwrap().iter() {
+            if l["digest"].is_string() {
+                layers.push((
+                    l["digest"].as_str().unwrap().to_string(),
+                    l["size"].as_i64().unwrap_or(0),
+                ));
+            }
+        }
+    }
+    Ok(layers)
+}
+
+// sum the (deduplicated) layer sizes of all images pulled in by the bundles selected by the isc
+// (the bundles, the min/max versions of the package or its channels)
+// and report the totals per package, per catalog and overall
+// an image that can't be resolved is reported as unknown and the estimate continues
+pub async fn process_estimate_size<T: DownloadImageInterface + Clone>(
+    reg_con: T,
+    log: &Logging,
+    dir: String,
+    token_enable: bool,
+    isc: &IscConfig,
+) -> Result<SizeReport, MirrorError> {
+    let t_impl = ImplTokenInterface {};
+    let mut tokens: HashMap<String, String> = HashMap::new();
+    let mut layer_sizes: HashMap<String, i64> = HashMap::new();
+    // layers already resolved per image (none when unknown), shared images are only fetched once
+    let mut image_layers: HashMap<String, Option<Vec<String>>> = HashMap::new();
+    let mut all_layers: HashSet<String> = HashSet::new();
+    let mut report = SizeReport::default();
+
+    log.hi("estimated mirror size (linux/amd64)");
+    for ctlog in isc.operators.iter() {
+        let config_dir = get_configs_dir(log, dir.clone(), ctlog.catalog.clone()).await?;
+        let mut catalog_layers: HashSet<String> = HashSet::new();
+        log.lo(&format!("catalog {}", ctlog.catalog));
+        for pkg in ctlog.packages.iter() {
+            let blobs = read_package_blobs(config_dir.clone(), pkg.name.clone())?;
+            let selected = selected_bundles(pkg, &get_bundles(&blobs), selected_head(pkg, &blobs));
+            if selected.len() == 0 {
+                log.warn(&format!(
+                    "[process_estimate_size] no bundles selected for package {} ({})",
+                    pkg.name, ctlog.catalog
+                ));
+            }
+            let mut images: Vec<String> = vec![];
+            for b in selected.iter() {
+                if b.image.is_some() && !images.contains(b.image.as_ref().unwrap()) {
+                    images.push(b.image.clone().unwrap());
+                }
+                for ri in b.related_images.clone().unwrap_or(vec![]).iter() {
+                    if !images.contains(&ri.image) {
+                        images.push(ri.image.clone());
+                    }
+                }
+            }
+            let mut package_layers: HashSet<String> = HashSet::new();
+            let mut package_unknown = 0;
+            for image in images.iter() {
+                if !image_layers.contains_key(image) {
+                    let res = fetch_image_layers(
+                        reg_con.clone(),
+                        log,
+                        &t_impl,
+                        &mut tokens,
+                        image,
+                        token_enable,
+                    )
+                    .await;
+                    if res.is_err() {
+                        log.warn(&format!(
+                            "[process_estimate_size] size of {} unknown {}",
+                            image,
+                            res.err().unwrap().to_string()
+                        ));
+                        report.unknown.push(image.clone());
+                        image_layers.insert(image.clone(), None);
+                        continue;
+                    }
+                    let mut digests = vec![];
+                    for (digest, size) in res.unwrap() {
+                        layer_sizes.insert(digest.clone(), size);
+                        digests.push(digest);
+                    }
+                    image_layers.insert(image.clone(), Some(digests));
+                }
+                match image_layers.get(image).unwrap() {
+                    Some(digests) => package_layers.extend(digests.iter().cloned()),
+                    None => package_unknown += 1,
+                }
+            }
+            let package_size = sum_layers(&package_layers, &layer_sizes);
+            let mut line = format!(
+                "  package {} ({} images) {}",
+                pkg.name,
+                images.len(),
+                format_size(package_size)
+            );
+            if package_unknown > 0 {
+                line = format!("{} ({} unknown)", line, package_unknown);
+            }
+            log.mid(&line);
+            report
+                .packages
+                .push((ctlog.catalog.clone(), pkg.name.clone(), package_size));
+            catalog_layers.extend(package_layers);
+        }
+        let catalog_size = sum_layers(&catalog_layers, &layer_sizes);
+        log.hi(&format!(
+            "  total for catalog {} {}",
+            ctlog.catalog,
+            format_size(catalog_size)
+        ));
+        report.catalogs.push((ctlog.catalog.clone(), catalog_size));
+        all_layers.extend(catalog_layers);
+    }
+    report.total = sum_layers(&all_layers, &layer_sizes);
+    log.hi(&format!(
+        "total (shared layers counted once) {}",
+        format_size(report.total)
+    ));
+    if report.unknown.len() > 0 {
+        log.warn(&format!(
+            "{} image(s) with an unknown size are not included in the totals",
+            report.unknown.len()
+        ));
+    }
+    Ok(report)
+}
+
+// resolve the layers of an image, tokens are requested once per registry
+async fn fetch_image_layers<T: DownloadImageInterface + Clone>(
+    reg_con: T,
+    log: &Logging,
+    t_impl: &ImplTokenInterface,
+    tokens: &mut HashMap<String, String>,
+    image: &String,
+    token_enable: bool,
+) -> Result<Vec<(String, i64)>, MirrorError> {
+    let parts = split_image(image.clone())?;
+    if !tokens.contains_key(&parts.registry) {
+        let token = get_token(
+            t_impl.clone(),
+            log,
+            parts.registry.clone(),
+            "".to_string(),
+            token_enable,
+        )
+        .await?;
+        tokens.insert(parts.registry.clone(), token);
+    }
+    let token = tokens.get(&parts.registry).unwrap().clone();
+    get_image_layers(reg_con, log, &parts, token).await
+}
+
+fn parse_manifest(data: String) -> Result<Value, MirrorError> {
+    let res = serde_json::from_str::<Value>(&data);
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[parse_manifest] {}",
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    Ok(res.unwrap())
+}
+
+fn sum_layers(layers: &HashSet<String>, sizes: &HashMap<String, i64>) -> i64 {
+    layers.iter().map(|l| sizes.get(l).unwrap_or(&0)).sum()
+}
+
+// human readable size
+fn format_size(size: i64) -> String {
+    let gb = size as f64 / (1024.0 * 1024.0 * 1024.0);
+    if gb >= 1.0 {
+        return format!("{:.2} GB", gb);
+    }
+    format!("{:.2} MB", size as f64 / (1024.0 * 1024.0))
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+    use async_trait::async_trait;
+    use std::fs;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn split_image_pass() {
+        let parts = split_image("registry.redhat.io/ns/name@sha256:abc".to_string()).unwrap();
+        assert_eq!(parts.registry, "registry.redhat.io");
+        assert_eq!(parts.repository, "ns/name");
+        assert_eq!(parts.reference, "sha256:abc");
+        let parts = split_image("localhost:5000/ns/name:v1.0".to_string()).unwrap();
+        assert_eq!(parts.registry, "localhost:5000");
+        assert_eq!(parts.repository, "ns/name");
+        assert_eq!(parts.reference, "v1.0");
+        assert_eq!(split_image("name".to_string()).is_err(), true);
+    }
+
+    #[derive(Clone)]
+    struct Fake {}
+
+    #[async_trait]
+    impl DownloadImageInterface for Fake {
+        async fn get_manifest(&self, url: String, _token: String) -> Result<String, MirrorError> {
+            if url.ends_with("sha256:list") {
+                return Ok("{ \"manifests\": [
+                        { \"digest\": \"sha256:arm\", \"platform\": { \"architecture\": \"arm64\" } },
+                        { \"digest\": \"sha256:amd\", \"platform\": { \"architecture\": \"amd64\" } } ] }"
+                        .to_string());
+            }
+            if url.ends_with("sha256:armonly") {
+                return Ok("{ \"manifests\": [
+                        { \"digest\": \"sha256:arm\", \"platform\": { \"architecture\": \"arm64\" } } ] }"
+                        .to_string());
+            }
+            if url.ends_with("sha256:amd") {
+                return Ok("{ \"config\": { \"digest\": \"sha256:c\", \"size\": 10 },
+                        \"layers\": [ { \"digest\": \"sha256:l1\", \"size\": 100 },
+                        { \"digest\": \"sha256:l2\", \"size\": 200 } ] }"
+                    .to_string());
+            }
+            if url.ends_with("sha256:other") {
+                return Ok("{ \"config\": { \"digest\": \"sha256:c2\", \"size\": 5 },
+                        \"layers\": [ { \"digest\": \"sha256:l1\", \"size\": 100 },
+                        { \"digest\": \"sha256:l3\", \"size\": 1000 } ] }"
+                    .to_string());
+            }
+            Err(MirrorError::new("unexpected url"))
+        }
+
+        async fn get_blob(
+            &self,
+            _log: &Logging,
+            _dir: String,
+            _url: String,
+            _token: String,
+            _verify_blob: bool,
+            _blob_sum: String,
+        ) -> Result<(), MirrorError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn get_image_layers_pass() {
+        let log = &Logging {
+            log_level: Level::DEBUG,
+        };
+        let parts = split_image("registry.test/ns/name@sha256:list".to_string()).unwrap();
+        let layers = aw!(get_image_layers(Fake {}, log, &parts, "".to_string()))
+            .expect("should resolve image layers");
+        assert_eq!(layers.len(), 3);
+        let total: i64 = layers.iter().map(|(_, s)| s).sum();
+        assert_eq!(total, 310);
+        // a manifest list without linux/amd64 is not estimated from another arch
+        let parts = split_image("registry.test/ns/name@sha256:armonly".to_string()).unwrap();
+        assert!(aw!(get_image_layers(Fake {}, log, &parts, "".to_string())).is_err());
+    }
+
+    #[test]
+    fn process_estimate_size_pass() {
+        let log = &Logging {
+            log_level: Level::DEBUG,
+        };
+        let dir = "./test-artifacts/estimate".to_string();
+        let config_dir = format!("{}/test-index/v0.0.1/amd64/cache/layer/configs", dir);
+        for (pkg, image, related) in [
+            ("a-operator", "sha256:list", "sha256:armonly"),
+            ("b-operator", "sha256:other", "sha256:missing"),
+        ] {
+            fs::create_dir_all(format!("{}/{}", config_dir, pkg)).expect("should create package");
+            fs::write(
+                format!("{}/{}/catalog.json", config_dir, pkg),
+                format!(
+                    "{{ \"schema\": \"olm.bundle\", \"name\": \"{0}.v0.0.1\", \"package\": \"{0}\",
+                      \"image\": \"registry.test/ns/bundle@{1}\",
+                      \"properties\": [ {{ \"type\": \"olm.package\", \"value\": {{ \"packageName\": \"{0}\", \"version\": \"0.0.1\" }} }} ],
+                      \"relatedImages\": [ {{ \"image\": \"registry.test/ns/related@{2}\" }} ] }}",
+                    pkg, image, related
+                ),
+            )
+            .expect("should write catalog");
+        }
+        // the same bundles selected by each isc style
+        let bundles = |name: &str| Package {
+            name: name.to_string(),
+            bundles: vec![Bundle {
+                name: format!("{}.v0.0.1", name),
+            }],
+            ..Default::default()
+        };
+        let channels = |name: &str| Package {
+            name: name.to_string(),
+            channels: vec![IscChannel {
+                name: "stable".to_string(),
+                min_version: Some("0.0.1".to_string()),
+                max_version: None,
+            }],
+            ..Default::default()
+        };
+        let versions = |name: &str| Package {
+            name: name.to_string(),
+            min_version: Some("0.0.1".to_string()),
+            max_version: Some("0.0.1".to_string()),
+            ..Default::default()
+        };
+        let catalog = "registry.test/ns/test-index:v0.0.1".to_string();
+        for style in [bundles, channels, versions] {
+            let mut isc = IscConfig::new("v2alpha1".to_string());
+            isc.operators = vec![Catalog {
+                catalog: catalog.clone(),
+                packages: vec![style("a-operator"), style("b-operator")],
+            }];
+            let report = aw!(process_estimate_size(
+                Fake {},
+                log,
+                dir.clone(),
+                false,
+                &isc
+            ))
+            .expect("should estimate size");
+            assert_eq!(
+                report.packages,
+                vec![
+                    (catalog.clone(), "a-operator".to_string(), 310),
+                    (catalog.clone(), "b-operator".to_string(), 1105)
+                ]
+            );
+            assert_eq!(report.catalogs, vec![(catalog.clone(), 1315)]);
+            // sha256:l1 is shared by both packages and only counted once
+            assert_eq!(report.total, 1315);
+            assert_eq!(
+                report.unknown,
+                vec![
+                    "registry.test/ns/related@sha256:armonly",
+                    "registry.test/ns/related@sha256:missing"
+                ]
+            );
+        }
+        fs::remove_dir_all(&dir).expect("should delete estimate test folder");
+    }
+}
@@ -1,26 +1,89 @@
 use crate::api::schema::*;
+use crate::fbc::reader::*;
 use crate::isc::generate::*;
+use crate::isc::output::catalog_file_name;
+use crate::upgradepath::dependencies::*;
+use crate::upgradepath::graph::*;
+use crate::upgradepath::missing::*;
+use crate::upgradepath::ocp::*;
 use custom_logger::*;
 use mirror_catalog::*;
+use mirror_error::MirrorError;
 use semver::{BuildMetadata, Prerelease, Version};
+use serde_json::Value;
+use std::cmp::*;
 use std::collections::HashMap;
-use std::{cmp::*, fs};
 use walkdir::WalkDir;
 
+// how the upgrade path is calculated and reported
+#[derive(Debug, Clone, Default)]
+pub struct UpgradepathOptions {
+    pub isc_style: String,
+    pub strict: bool,
+    pub skip_dependencies: bool,
+    pub target_ocp: Option<String>,
+    pub exclude_deprecated: bool,
+    // export the channel graphs (dot or mermaid) to the output dir
+    pub graph: Option<String>,
+    pub output_dir: String,
+}
+
+// the package entry and the computed upgrade paths
+#[derive(Debug, Clone, Default)]
+pub struct ChannelInfo {
+    pub package: Package,
+    // the upgrade path (bundle names) of the selected channel
+    pub path: Vec<String>,
+    // the upgrade path of every channel
+    pub channel_paths: HashMap<String, Vec<String>>,
+    // deprecated bundles on the upgrade path of the selected channel (left out of path when excluded)
+    pub deprecated: Vec<String>,
+}
+
 pub async fn process_upgradepath(
     log: &Logging,
     api_version: String,
     dir: String,
-    output_dir: String,
     filter: FilterConfig,
-) {
+    opts: &UpgradepathOptions,
+) -> Result<IscConfig, MirrorError> {
+    let isc_style = opts.isc_style.clone();
+    if opts.target_ocp.is_some() && parse_ocp_version(opts.target_ocp.as_ref().unwrap()).is_none() {
+        return Err(MirrorError::new(&format!(
+            "[process_upgradepath] invalid target openshift version {}",
+            opts.target_ocp.as_ref().unwrap()
+        )));
+    }
     let mut isc = IscConfig::new(api_version.clone());
+    if filter.platform.is_some() {
+        isc.platform = build_platform(
+            log,
+            filter.catalogs.clone(),
+            filter.platform.clone().unwrap(),
+        );
+    }
+    if filter.additional_images.is_some() {
+        for img in filter.additional_images.clone().unwrap().iter() {
+            isc.additional_images.push(Image {
+                name: img.name.clone(),
+            });
+        }
+    }
+
+    // packages found in at least one of their targeted catalogs
+    let mut found: Vec<String> = vec![];
+    let mut reports: Vec<MissingReport> = vec![];
+    let mut unresolved = 0;
 
     // list the operators found in the filter
     for catalog in filter.clone().catalogs {
         let mut ctlog = Catalog::default();
         ctlog.catalog = catalog.clone();
         log.lo(&format!("catalog {}", catalog));
+        // package names found under 'configs' and the requested ones found in this catalog
+        let mut available: Vec<String> = vec![];
+        let mut found_in_catalog: Vec<String> = vec![];
+        let mut config_dir = String::from("");
         let last = catalog.split('/').last().unwrap();
         let index_dir = last.replace(":", "/");
         let catalog_dir = format!("{}/{}/{}/", dir.clone(), &index_dir, "/amd64/cache/");
@@ -30,15 +93,46 @@ pub async fn process_upgradepath(
             let check = file.as_ref().unwrap().clone();
             if check.clone().path().is_dir() {
                 let f = file.unwrap().clone().path().display().to_string();
-                if f.contains("updated-configs") {
+                if f.ends_with("/configs") {
+                    config_dir = f.clone();
+                }
+                if f.contains("/configs/") && !f.contains("/updated-configs") {
+                    let name = f.split("/configs/").nth(1).unwrap();
+                    if !name.contains('/') {
+                        available.push(name.to_string());
+                    }
+                }
+                if f.ends_with("/updated-configs") {
                     if filter.operators.is_some() {
+                        // get the package name from the path i.e <configs>/<package>/updated-configs
+                        let hold = f.split("/configs/").nth(1).unwrap();
+                        let package = hold.split("/updated-configs").nth(0).unwrap();
                         for component in filter.clone().operators.unwrap() {
-                            if f.contains(&component.name) && f.contains("updated-configs") {
-                                let dc =
-                                    DeclarativeConfig::get_declarativeconfig_map(f.clone() + "/");
-                                log.trace(&format!("declarative config keys {:#?}", dc.keys()));
-                                let pkg = list_channel_info(log, dc, component).unwrap();
-                                ctlog.packages.push(pkg.clone());
+                            if component.matches_package(package)
+                                && component.targets_catalog(&catalog)
+                            {
+                                if !found.contains(&component.name) {
+                                    found.push(component.name.clone());
+                                }
+                                found_in_catalog.push(component.name.clone());
+                                // a package matched by several entries (i.e name and pattern)
+                                // is only evaluated for the first one
+                                if ctlog.packages.iter().any(|p| p.name == package) {
+                                    continue;
+                                }
+                                let mut operator = component.clone();
+                                operator.name = package.to_string();
+                                let pkg = evaluate_package(
+                                    log,
+                                    opts,
+                                    &catalog,
+                                    &config_dir,
+                                    &f,
+                                    operator,
+                                )?;
+                                if pkg.is_some() {
+                                    ctlog.packages.push(pkg.unwrap());
+                                }
                             }
                         }
                     } else {
@@ -46,37 +140,318 @@ pub async fn process_upgradepath(
                         // get the component name
                         let hold = f.split("/configs/").nth(1).unwrap();
                         let component = hold.split("/updated-configs").nth(0).unwrap();
-                        let dc = DeclarativeConfig::get_declarativeconfig_map(f.clone() + "/");
-                        log.trace(&format!("declarative config keys {:#?}", dc.keys()));
                         let operator = FilterOperator {
                             name: component.to_string(),
                             channel: Some("all".to_string()),
                             from_version: Some("0.0.0".to_string()),
+                            catalogs: None,
                         };
-                        let pkg = list_channel_info(log, dc, operator).unwrap();
-                        ctlog.packages.push(pkg.clone());
+                        let pkg = evaluate_package(log, opts, &catalog, &config_dir, &f, operator)?;
+                        if pkg.is_some() {
+                            ctlog.packages.push(pkg.unwrap());
+                        }
                     }
                 }
             }
         }
+        // add the packages required by the selected bundles (filtered catalogs only,
+        // listing all the operators already includes them)
+        if filter.operators.is_some() && !opts.skip_dependencies && config_dir.len() > 0 {
+            let report = resolve_dependencies(
+                log,
+                catalog.clone(),
+                config_dir.clone(),
+                &mut ctlog.packages,
+                isc_style.clone(),
+            )?;
+            report.display(log);
+            unresolved += report.unresolved();
+        }
         isc.operators.push(ctlog.clone());
+        // report the requested packages missing from this catalog
+        let missing: Vec<String> = filter
+            .operators
+            .clone()
+            .unwrap_or(vec![])
+            .iter()
+            .filter(|o| o.targets_catalog(&catalog) && !found_in_catalog.contains(&o.name))
+            .map(|o| o.name.clone())
+            .collect();
+        let report = MissingReport::new(catalog.clone(), missing, &available);
+        report.display(log);
+        reports.push(report);
         // print a new line, separates each catalog
         println!("");
     }
-    // finally autogenerate the isc
-    let contents = isc.to_yaml();
-    log.info(&format!("{}", contents.clone()));
-    // save to file
-    let file = &format!("{}/auto-generated.yaml", output_dir);
-    fs::write(file, contents).expect("should save autogenerated isc")
+    for component in filter.operators.clone().unwrap_or(vec![]).iter() {
+        if !found.contains(&component.name) {
+            let targets = match component.catalogs.as_ref() {
+                Some(c) => c.join(", "),
+                None => filter.catalogs.join(", "),
+            };
+            log.warn(&format!(
+                "package '{}' not found in any targeted catalog ({})",
+                component.name, targets
+            ));
+        }
+    }
+    let missing_count: usize = reports.iter().map(|r| r.missing.len()).sum();
+    if opts.strict && missing_count > 0 {
+        return Err(MirrorError::new(&format!(
+            "[process_upgradepath] {} requested package(s) missing from targeted catalogs (strict mode)",
+            missing_count
+        )));
+    }
+    if opts.strict && unresolved > 0 {
+        return Err(MirrorError::new(&format!(
+            "[process_upgradepath] {} unresolved dependencies (strict mode)",
+            unresolved
+        )));
+    }
+    isc.validate()?;
+    Ok(isc)
+}
+
+// build the platform section, one channel per catalog version (i.e v4.15 -> stable-4.15)
+// channels outside of the min/max range are dropped, the min (max) version is set on
+// the channel of the same minor version
+fn build_platform(
+    log: &Logging,
+    catalogs: Vec<String>,
+    filter: FilterPlatform,
+) -> Option<Platform> {
+    let prefix = filter.channel.unwrap_or("stable".to_string());
+    let mut versions: Vec<Version> = vec![];
+    for catalog in catalogs.iter() {
+        let tag = catalog.split(':').last().unwrap().trim_start_matches('v');
+        let res = Version::parse(&format!("{}.0", tag));
+        if res.is_err() || catalog.split('/').last().unwrap().find(':').is_none() {
+            log.warn(&format!(
+                "[build_platform] unable to derive an openshift version from catalog {}",
+                catalog
+            ));
+            continue;
+        }
+        let v = res.unwrap();
+        if !versions.contains(&v) {
+            versions.push(v);
+        }
+    }
+    let min = platform_version(log, "minVersion", &filter.min_version);
+    let max = platform_version(log, "maxVersion", &filter.max_version);
+    versions.retain(|v| {
+        min.as_ref()
+            .is_none_or(|m| (v.major, v.minor) >= (m.major, m.minor))
+            && max
+                .as_ref()
+                .is_none_or(|m| (v.major, v.minor) <= (m.major, m.minor))
+    });
+    if versions.len() == 0 {
+        log.warn("[build_platform] no platform channels, skipping platform section");
+        return None;
+    }
+    versions.sort();
+    let mut channels: Vec<PlatformChannel> = vec![];
+    for v in versions.iter() {
+        let same_minor = |m: &Option<Version>| {
+            m.as_ref()
+                .is_some_and(|m| (m.major, m.minor) == (v.major, v.minor))
+        };
+        channels.push(PlatformChannel {
+            name: format!("{}-{}.{}", prefix, v.major, v.minor),
+            min_version: filter.min_version.clone().filter(|_| same_minor(&min)),
+            max_version: filter.max_version.clone().filter(|_| same_minor(&max)),
+            channel_type: Some("ocp".to_string()),
+        });
+    }
+    Some(Platform {
+        architectures: filter.architectures,
+        channels,
+        graph: filter.graph,
+    })
+}
+
+// parse a platform min/max version (i.e 4.15.3), invalid versions are ignored
+fn platform_version(log: &Logging, field: &str, version: &Option<String>) -> Option<Version> {
+    let v = version.as_ref()?;
+    let res = Version::parse(v.trim_start_matches('v'));
+    if res.is_err() {
+        log.warn(&format!(
+            "[build_platform] platform {} {} is not a valid version, ignoring it",
+            field, v
+        ));
+        return None;
+    }
+    Some(res.unwrap())
+}
+
+// calculate the upgrade path of a package (updated_configs is <configs>/<package>/updated-configs)
+// returns none when the package is deprecated and deprecated content is excluded
+fn evaluate_package(
+    log: &Logging,
+    opts: &UpgradepathOptions,
+    catalog: &String,
+    config_dir: &String,
+    updated_configs: &String,
+    operator: FilterOperator,
+) -> Result<Option<Package>, MirrorError> {
+    // the raw blobs hold the properties and deprecations dropped from the declarative config
+    let res = read_package_blobs(config_dir.clone(), operator.name.clone());
+    let mut blobs: Vec<Value> = vec![];
+    if res.is_err() {
+        log.warn(&format!(
+            "[evaluate_package] {}",
+            res.err().unwrap().to_string().to_lowercase()
+        ));
+    } else {
+        blobs = res.unwrap();
+    }
+    let deprecations = get_deprecations(&blobs);
+    if let Some(msg) = deprecations.package() {
+        log.warn(&format!(
+            "operator '{}' is deprecated : {}",
+            operator.name,
+            msg.trim()
+        ));
+        if opts.exclude_deprecated {
+            log.warn(&format!(
+                "operator '{}' excluded from the imagesetconfig",
+                operator.name
+            ));
+            return Ok(None);
+        }
+    }
+    let from_version = operator.from_version.clone();
+    let dc = DeclarativeConfig::get_declarativeconfig_map(updated_configs.clone() + "/");
+    log.trace(&format!("declarative config keys {:#?}", dc.keys()));
+    let res = list_channel_info(
+        log,
+        dc,
+        operator,
+        opts.isc_style.clone(),
+        &deprecations,
+        opts.exclude_deprecated,
+    );
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[evaluate_package] {}",
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    let info = res.unwrap();
+    if opts.graph.is_some() {
+        export_graphs(log, opts, catalog, &info, from_version, &blobs)?;
+    }
+    if !keep_package(log, opts, &info)? {
+        return Ok(None);
+    }
+    check_target_ocp(log, opts, &info.package.name, &info.path, &blobs);
+    Ok(Some(info.package))
+}
+
+// a package without a selection would mirror the default channel head, so a package with an
+// empty upgrade path (channel not found, no bundles from the fromVersion, only deprecated
+// bundles) is left out, with --strict it fails the run (unless deprecated bundles were excluded)
+fn keep_package(
+    log: &Logging,
+    opts: &UpgradepathOptions,
+    info: &ChannelInfo,
+) -> Result<bool, MirrorError> {
+    if info.path.len() > 0 {
+        return Ok(true);
+    }
+    if opts.exclude_deprecated && info.deprecated.len() > 0 {
+        log.warn(&format!(
+            "operator '{}' excluded from the imagesetconfig (the upgrade path only has deprecated bundles)",
+            info.package.name
+        ));
+        return Ok(false);
+    }
+    if opts.strict {
+        return Err(MirrorError::new(&format!(
+            "[keep_package] operator '{}' has an empty upgrade path (strict mode)",
+            info.package.name
+        )));
+    }
+    log.warn(&format!(
+        "operator '{}' excluded from the imagesetconfig (empty upgrade path, check the channel and fromVersion)",
+        info.package.name
+    ));
+    Ok(false)
+}
+
+// write the full upgrade graph of every channel of the package
+// to <output-dir>/graphs/<catalog>/<package>-<channel>.(dot|mmd)
+fn export_graphs(
+    log: &Logging,
+    opts: &UpgradepathOptions,
+    catalog: &String,
+    info: &ChannelInfo,
+    from_version: Option<String>,
+    blobs: &Vec<Value>,
+) -> Result<(), MirrorError> {
+    let dir = format!(
+        "{}/graphs/{}",
+        opts.output_dir,
+        catalog_file_name(catalog).trim_end_matches(".yaml")
+    );
+    let bundles = get_bundles(blobs);
+    for channel in get_channels(blobs).iter() {
+        // the node matching the fromVersion in the filter (if any)
+        let from = match from_version.as_ref().filter(|v| v.as_str() != "0.0.0") {
+            Some(v) => {
+                let semver = build_semver(v.clone())?;
+                channel
+                    .entries
+                    .iter()
+                    .find(|e| build_semver(bundle_version(&e.name)).is_ok_and(|s| s == semver))
+                    .map(|e| e.name.clone())
+            }
+            None => None,
+        };
+        let path = info
+            .channel_paths
+            .get(&channel.name)
+            .cloned()
+            .unwrap_or(vec![]);
+        let graph = ChannelGraph::new(info.package.name.clone(), channel, &bundles, from, path);
+        let file = write_graph(&dir, &graph, opts.graph.as_ref().unwrap())?;
+        log.debug(&format!("[export_graphs] graph written to {}", file));
+    }
+    Ok(())
+}
+
+// report the bundles on the path blocking the upgrade to the target openshift version
+fn check_target_ocp(
+    log: &Logging,
+    opts: &UpgradepathOptions,
+    package: &str,
+    path: &[String],
+    blobs: &[Value],
+) {
+    if opts.target_ocp.is_none() || path.len() == 0 {
+        return;
+    }
+    let bundles = get_bundles(blobs);
+    let report = OcpReport::new(
+        package.to_string(),
+        opts.target_ocp.clone().unwrap(),
+        path,
+        &bundles,
+    );
+    report.display(log);
 }
 
 // iterate through object and display values
+// returns the package and the upgrade path (bundle names) of the selected channel
 pub fn list_channel_info(
     log: &Logging,
     dc_map: HashMap<String, DeclarativeConfig>,
     filter: FilterOperator,
-) -> Result<Package, Box<dyn std::error::Error>> {
+    isc_style: String,
+    deprecations: &OlmDeprecations,
+    exclude_deprecated: bool,
+) -> Result<ChannelInfo, Box<dyn std::error::Error>> {
     // check to see if filter.from_version is valid (or empty)
     let mut current_semver = Version::parse("0.0.0").unwrap();
     let mut current_version = String::from("0.0.0");
@@ -90,13 +465,22 @@ pub fn list_channel_info(
 
     if filter.from_version.is_some() {
         current_version = filter.from_version.unwrap();
-        current_semver = Version::parse(&current_version).unwrap();
+        let res = Version::parse(&current_version);
+        if res.is_err() {
+            return Err(Box::new(MirrorError::new(&format!(
+                "[list_channel_info] operator '{}' fromVersion {} : {}",
+                filter.name,
+                current_version,
+                res.err().unwrap().to_string().to_lowercase()
+            ))));
+        }
+        current_semver = res.unwrap();
     }
 
     // check to see if filter.channel is valid (or empty)
-    let mut _current_channel = String::from("all");
+    let mut current_channel = String::from("all");
     if filter.channel.is_some() {
-        _current_channel = filter.channel.unwrap();
+        current_channel = filter.channel.unwrap();
     }
 
     // get default channel
@@ -106,6 +490,12 @@ pub fn list_channel_info(
     for k in keys {
         if k.contains("olm.package") {
             let pkg = dc_map.get(k).unwrap();
+            if pkg.default_channel.is_none() {
+                return Err(Box::new(MirrorError::new(&format!(
+                    "[list_channel_info] operator '{}' has no defaultChannel",
+                    filter.name
+                ))));
+            }
             default_channel = pkg.default_channel.clone().unwrap();
             break;
         }
@@ -114,77 +504,53 @@ pub fn list_channel_info(
     log.ex(&format!("operator '{}'", filter.name));
     log.ex(&format!("  defaultChannel {:?}", default_channel.clone()));
 
+    // the channel used to build the isc entry, only the channels style
+    // honours the channel set in the filter
+    let mut selected_channel = default_channel.clone();
+    if isc_style == "channels" && current_channel != "all" {
+        selected_channel = current_channel.clone();
+    }
+    let mut selected_path: Vec<String> = vec![];
+    let mut channel_paths: HashMap<String, Vec<String>> = HashMap::new();
+    // deprecated bundles of the selected path
+    let mut deprecated: Vec<String> = vec![];
+    package.name = filter.name.clone();
+
     let mut available_versions: Vec<String> = vec![];
+    let mut channel_names: Vec<String> = vec![];
     // iterate through the dc - look specifically for olm.channel schema
     for (k, v) in dc_map {
         if k.contains("olm.channel") {
-            let mut current: Vec<ChannelEntry> = vec![];
-            let mut replace: Vec<String> = vec![];
-
-            // entries contain all the relevant upgrade path info
-            for y in v.entries.unwrap() {
-                // get the bundle semver
+            let channel_name = v.name.clone().unwrap_or(k.clone());
+            channel_names.push(channel_name.clone());
+            if v.entries.is_none() {
+                return Err(Box::new(MirrorError::new(&format!(
+                    "[list_channel_info] operator '{}' channel {} has no entries",
+                    filter.name, channel_name
+                ))));
+            }
+            let entries = v.entries.unwrap();
+            for y in entries.iter() {
                 if !available_versions.contains(&y.name.clone()) {
                     available_versions.insert(0, y.name.clone());
                 }
-                let mut semver_tmp = String::from("0.0.0");
-                if semver_tmp != "0.0.0" {}
-                if y.name.clone().contains(".v") {
-                    semver_tmp = y.name.split(".v").nth(1).unwrap().to_string();
-                } else {
-                    // the case when we don't have ".v" in the catalog
-                    // oh the joys of giving devs free range :(
-                    // for now we only do major,min,patch,pre and ignore build versions
-                    let n = y.name.split(".").nth(0).unwrap().to_string();
-                    semver_tmp = y
-                        .name
-                        .split(&n)
-                        .nth(1)
-                        .unwrap()
-                        .to_string()
-                        .get(1..)
-                        .unwrap()
-                        .to_string();
-                }
-                let bundle_semver = build_semver(semver_tmp);
-                let res = current_semver.cmp(&bundle_semver);
-                if res != Ordering::Greater {
-                    current.insert(0, y.clone());
-                    if y.replaces.is_some() {
-                        replace.insert(0, y.replaces.unwrap());
-                    }
-                }
             }
-            let mut stage: Vec<ChannelEntry> = vec![];
-            let mut updated: Vec<ChannelEntry> = vec![];
+            let refs: Vec<PathEntry> = entries
+                .iter()
+                .map(|e| (&e.name, e.replaces.as_ref(), e.skips.as_ref()))
+                .collect();
+            let updated: Vec<ChannelEntry> = upgrade_path_indexes(&refs, &current_semver)?
+                .iter()
+                .rev()
+                .map(|i| entries[*i].clone())
+                .collect();
             log.lo(&format!("  channel name {}", k));
-
-            // TODO: this can be re-factored into a couple of lines
-            for ce in current.iter() {
-                let mut found = false;
-                for r in replace.iter() {
-                    if r == &ce.name {
-                        found = true;
-                    }
-                }
-                if !found {
-                    stage.insert(0, ce.clone());
-                }
-            }
-            for n in stage.iter() {
-                let mut found = false;
-                for ce in current.iter() {
-                    if ce.skips.is_some() {
-                        for s in ce.skips.clone().unwrap().iter() {
-                            if s == &n.name {
-                                found = true;
-                            }
-                        }
-                    }
-                }
-                if !found {
-                    updated.insert(0, n.clone());
-                }
+            if let Some(msg) = deprecations.channel(&channel_name) {
+                log.warn(&format!(
+                    "    channel {} is deprecated : {}",
+                    channel_name,
+                    msg.trim()
+                ));
             }
 
             // sort the available versions vector by semver
@@ -198,18 +564,33 @@ pub fn list_channel_info(
             if current_version == "0.0.0" {
                 upgrade_str = "?".to_string();
             }
+            channel_paths.insert(
+                channel_name.clone(),
+                updated.iter().map(|p| p.name.clone()).collect(),
+            );
             for p in updated.iter() {
                 upgrade_str = upgrade_str + " -> " + &p.name;
                 if p.skip_range.is_some() {
                     skip_range = skip_range + " : " + &p.skip_range.clone().unwrap();
                 }
-                // build for default channel
-                if k.contains(&default_channel) {
-                    package.name = filter.name.clone();
-                    bundle.name = p.name.clone();
-                    log.trace(&format!("{:#?}", bundle));
-                    if !package.bundles.contains(&bundle) {
-                        package.bundles.push(bundle.clone());
+                if deprecations.bundle(&p.name).is_some() {
+                    upgrade_str = upgrade_str + " (deprecated)";
+                    if channel_name == selected_channel {
+                        deprecated.push(p.name.clone());
+                    }
+                    if exclude_deprecated {
+                        continue;
+                    }
+                }
+                // build for the selected (default) channel
+                if channel_name == selected_channel {
+                    selected_path.push(p.name.clone());
+                    if isc_style == "bundles" {
+                        bundle.name = p.name.clone();
+                        log.trace(&format!("{:#?}", bundle));
+                        if !package.bundles.contains(&bundle) {
+                            package.bundles.push(bundle.clone());
+                        }
                     }
                 }
             }
@@ -220,7 +601,123 @@ pub fn list_channel_info(
             }
         }
     }
-    Ok(package.clone())
+    // a channel set in the filter that the package doesn't have selects nothing
+    if current_channel != "all" && !channel_names.contains(&current_channel) {
+        channel_names.sort();
+        log.warn(&format!(
+            "  channel '{}' not found in operator '{}' (available channels {})",
+            current_channel,
+            filter.name,
+            channel_names.join(", ")
+        ));
+    }
+    selected_path.sort_unstable_by(|a, b| {
+        build_semver(bundle_version(a))
+            .ok()
+            .cmp(&build_semver(bundle_version(b)).ok())
+    });
+    // express the upgrade path as versions (channels or package level)
+    if selected_path.len() > 0 && isc_style != "bundles" {
+        let min_version = Some(bundle_version(selected_path.first().unwrap()));
+        let max_version = Some(bundle_version(selected_path.last().unwrap()));
+        // version ranges can't leave out the deprecated bundles in between
+        let min = build_semver(bundle_version(selected_path.first().unwrap()))?;
+        let max = build_semver(bundle_version(selected_path.last().unwrap()))?;
+        for name in deprecated.iter().filter(|_| exclude_deprecated) {
+            let v = build_semver(bundle_version(name))?;
+            if v > min && v < max {
+                log.warn(&format!(
+                    "    deprecated bundle {} is within the {} range (use --isc-style bundles to exclude it)",
+                    name, isc_style
+                ));
+            }
+        }
+        if isc_style == "channels" {
+            package.channels.push(IscChannel {
+                name: selected_channel.clone(),
+                min_version,
+                max_version,
+            });
+            // the default channel must be overridden when it is not mirrored
+            if selected_channel != default_channel {
+                package.default_channel = Some(selected_channel.clone());
+            }
+        } else {
+            package.min_version = min_version;
+            package.max_version = max_version;
+        }
+    }
+    Ok(ChannelInfo {
+        package: package.clone(),
+        path: selected_path,
+        channel_paths,
+        deprecated,
+    })
+}
+
+// the upgrade path of a channel from a version (same rules as list_channel_info)
+// channel entry as (name, replaces, skips)
+pub type PathEntry<'a> = (&'a String, Option<&'a String>, Option<&'a Vec<String>>);
+
+// the indexes of the entries at or above the version that aren't replaced or
+// skipped by another one of them (the upgrade path rules of list_channel_info)
+pub fn upgrade_path_indexes(
+    entries: &[PathEntry],
+    from: &Version,
+) -> Result<Vec<usize>, MirrorError> {
+    let mut current: Vec<usize> = vec![];
+    for (i, e) in entries.iter().enumerate() {
+        if &build_semver(bundle_version(e.0))? >= from {
+            current.push(i);
+        }
+    }
+    Ok(current
+        .iter()
+        .filter(|i| {
+            let name = entries[**i].0;
+            !current.iter().any(|c| {
+                entries[*c].1 == Some(name) || entries[*c].2.is_some_and(|s| s.contains(name))
+            })
+        })
+        .copied()
+        .collect())
+}
+
+// the upgrade path of a channel from a version (sorted by semver)
+pub fn channel_upgrade_path(
+    channel: &OlmChannel,
+    from_version: &String,
+) -> Result<Vec<String>, MirrorError> {
+    let refs: Vec<PathEntry> = channel
+        .entries
+        .iter()
+        .map(|e| (&e.name, e.replaces.as_ref(), e.skips.as_ref()))
+        .collect();
+    let mut path: Vec<String> = upgrade_path_indexes(&refs, &build_semver(from_version.clone())?)?
+        .iter()
+        .map(|i| channel.entries[*i].name.clone())
+        .collect();
+    path.sort_unstable_by(compare_semver);
+    Ok(path)
+}
+
+// utility to get the version part of a bundle name
+// i.e jaeger-operator.v1.42.0-5 -> 1.42.0-5
+pub fn bundle_version(name: &String) -> String {
+    if name.contains(".v") {
+        return name.split(".v").nth(1).unwrap().to_string();
+    }
+    // the case when we don't have ".v" in the catalog
+    // oh the joys of giving devs free range :(
+    // for now we only do major,min,patch,pre and ignore build versions
+    let n = name.split(".").nth(0).unwrap().to_string();
+    name.split(&n)
+        .nth(1)
+        .unwrap()
+        .to_string()
+        .get(1..)
+        .unwrap()
+        .to_string()
 }
 
 // utility sort by semver
@@ -228,22 +725,21 @@ fn compare_semver(a: &String, b: &String) -> Ordering {
     if a.contains(".v") && b.contains(".v") {
         let w = a.split(".v").nth(1).unwrap();
         let x = b.split(".v").nth(1).unwrap();
-        let y = build_semver(w.to_string());
-        let z = build_semver(x.to_string());
+        let y = build_semver(w.to_string()).ok();
+        let z = build_semver(x.to_string()).ok();
         return y.cmp(&z);
     }
     let w = a.split(".").nth(1).unwrap();
     let x = b.split(".").nth(1).unwrap();
-    let y = build_semver(w.to_string());
-    let z = build_semver(x.to_string());
+    let y = build_semver(w.to_string()).ok();
+    let z = build_semver(x.to_string()).ok();
     return y.cmp(&z);
 }
 
 // utility to build a more complex semver
-fn build_semver(semver_str: String) -> semver::Version {
-    let major: &str;
-    let minor: &str;
-    let mut patch: &str;
+// a missing minor or patch is taken as 0 (i.e 1.2 -> 1.2.0)
+fn build_semver(semver_str: String) -> Result<semver::Version, MirrorError> {
+    let invalid = || MirrorError::new(&format!("[build_semver] invalid version '{}'", semver_str));
     let mut version: Version = Version {
         major: 0,
         minor: 0,
@@ -253,30 +749,382 @@ fn build_semver(semver_str: String) -> semver::Version {
     };
     let mut i = semver_str.split(".");
     if i.clone().count() > 1 {
-        major = i.nth(0).unwrap();
-        minor = i.nth(0).unwrap();
-        patch = i.nth(0).unwrap();
+        let major = i.next().unwrap();
+        let minor = i.next().unwrap();
+        let mut patch = i.next().unwrap_or("0");
         let mut tmp = String::from("");
         if patch.contains("-") {
             let mut x = patch.split("-");
-            patch = x.nth(0).unwrap();
-            tmp = x.nth(0).unwrap().to_string();
+            patch = x.next().unwrap();
+            tmp = x.next().unwrap().to_string();
         }
-        version.major = major.parse().unwrap();
-        version.minor = minor.parse().unwrap();
-        version.patch = patch.parse().unwrap();
-        version.pre = Prerelease::new(&tmp).unwrap();
-        version.build = BuildMetadata::EMPTY;
+        version.major = major.parse().map_err(|_| invalid())?;
+        version.minor = minor.parse().map_err(|_| invalid())?;
+        version.patch = patch.parse().map_err(|_| invalid())?;
+        version.pre = Prerelease::new(&tmp).map_err(|_| invalid())?;
     } else {
         // this is some real kaka right here
         // in some of the catalog.json files we have incoherent semver (some use v some don't)
         // the wonderful world of devs with free range :)
-        if semver_str.contains("v") {
-            let mut n = semver_str.split("v");
-            version.major = n.nth(1).unwrap().parse().unwrap();
-        } else {
-            version.major = i.nth(0).unwrap().parse().unwrap();
+        let major = match semver_str.contains("v") {
+            true => semver_str.split("v").nth(1).unwrap(),
+            false => semver_str.as_str(),
+        };
+        version.major = major.parse().map_err(|_| invalid())?;
+    }
+    Ok(version)
+}
+
+// declarative config map (as read from the updated configs) built from the raw package
+// blobs, keyed on schema=name
+pub fn declarativeconfig_map(blobs: &[Value]) -> HashMap<String, DeclarativeConfig> {
+    let mut map = HashMap::new();
+    for b in blobs.iter() {
+        let schema = b["schema"].as_str().unwrap_or_default();
+        if schema != "olm.package" && schema != "olm.channel" {
+            continue;
+        }
+        let res = serde_json::from_value::<DeclarativeConfig>(b.clone());
+        if let Ok(dc) = res {
+            map.insert(
+                format!("{}={}", schema, b["name"].as_str().unwrap_or_default()),
+                dc,
+            );
+        }
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+    use serde_json::json;
+
+    // declarative config of a package with a stable (default) and a fast channel
+    fn dc_map() -> HashMap<String, DeclarativeConfig> {
+        let blobs = vec![
+            json!({ "schema": "olm.package", "name": "test-operator", "defaultChannel": "stable" }),
+            json!({ "schema": "olm.channel", "name": "stable", "package": "test-operator",
+                "entries": [
+                    { "name": "test-operator.v1.0.0" },
+                    { "name": "test-operator.v1.1.0", "skipRange": ">=1.0.0 <1.1.0" },
+                    { "name": "test-operator.v1.2.0", "replaces": "test-operator.v1.0.0" }
+                ] }),
+            json!({ "schema": "olm.channel", "name": "fast", "package": "test-operator",
+                "entries": [
+                    { "name": "test-operator.v1.2.0" },
+                    { "name": "test-operator.v1.3.0", "skips": [ "test-operator.v1.2.0" ] },
+                    { "name": "test-operator.v2.0.0" }
+                ] }),
+        ];
+        declarativeconfig_map(&blobs)
+    }
+
+    #[test]
+    fn keep_package_pass() {
+        let log = &Logging {
+            log_level: Level::INFO,
+        };
+        let no_deprecations = OlmDeprecations::default();
+        let mut opts = UpgradepathOptions::default();
+        let info = list_channel_info(
+            log,
+            dc_map(),
+            filter(Some("stable"), None),
+            "channels".to_string(),
+            &no_deprecations,
+            false,
+        )
+        .unwrap();
+        assert!(keep_package(log, &opts, &info).unwrap());
+
+        // a channel missing from the package selects nothing, the package is left out
+        let info = list_channel_info(
+            log,
+            dc_map(),
+            filter(Some("missing"), None),
+            "channels".to_string(),
+            &no_deprecations,
+            false,
+        )
+        .unwrap();
+        assert_eq!(info.path.len(), 0);
+        assert!(!keep_package(log, &opts, &info).unwrap());
+        opts.strict = true;
+        assert!(keep_package(log, &opts, &info).is_err());
+
+        // excluding deprecated bundles on purpose isn't a strict failure
+        let deprecations: OlmDeprecations = serde_json::from_value(json!({
+            "schema": "olm.deprecations", "package": "test-operator",
+            "entries": [
+                { "reference": { "schema": "olm.bundle", "name": "test-operator.v1.2.0" },
+                  "message": "deprecated" }
+            ] }))
+        .unwrap();
+        let info = list_channel_info(
+            log,
+            dc_map(),
+            filter(Some("stable"), Some("1.2.0")),
+            "channels".to_string(),
+            &deprecations,
+            true,
+        )
+        .unwrap();
+        assert_eq!(info.path.len(), 0);
+        opts.exclude_deprecated = true;
+        assert!(!keep_package(log, &opts, &info).unwrap());
+    }
+
+    #[test]
+    fn build_semver_pass() {
+        assert_eq!(
+            build_semver("1.2.3-4".to_string()).unwrap(),
+            Version::parse("1.2.3-4").unwrap()
+        );
+        // a missing patch is taken as 0
+        assert_eq!(
+            build_semver("1.2".to_string()).unwrap(),
+            Version::parse("1.2.0").unwrap()
+        );
+        assert_eq!(build_semver("v3".to_string()).unwrap().major, 3);
+        assert!(build_semver("1.x".to_string()).is_err());
+        assert!(build_semver("latest".to_string()).is_err());
+    }
+
+    fn filter(channel: Option<&str>, from_version: Option<&str>) -> FilterOperator {
+        FilterOperator {
+            name: "test-operator".to_string(),
+            channel: channel.map(|c| c.to_string()),
+            from_version: from_version.map(|v| v.to_string()),
+            catalogs: None,
         }
     }
-    version
+
+    #[test]
+    fn build_platform_pass() {
+        let log = &Logging {
+            log_level: Level::INFO,
+        };
+        let catalogs = vec![
+            "registry.redhat.io/redhat/redhat-operator-index:v4.16".to_string(),
+            "registry.redhat.io/redhat/redhat-operator-index:v4.14".to_string(),
+            "registry.redhat.io/redhat/redhat-operator-index:v4.15".to_string(),
+        ];
+        let platform = |min: Option<&str>, max: Option<&str>| FilterPlatform {
+            channel: Some("eus".to_string()),
+            min_version: min.map(|v| v.to_string()),
+            max_version: max.map(|v| v.to_string()),
+            graph: None,
+            architectures: None,
+        };
+        let channel = |name: &str, min: Option<&str>, max: Option<&str>| PlatformChannel {
+            name: name.to_string(),
+            min_version: min.map(|v| v.to_string()),
+            max_version: max.map(|v| v.to_string()),
+            channel_type: Some("ocp".to_string()),
+        };
+
+        // the catalogs are not ordered, the range still spans the lowest to the highest channel
+        let res = build_platform(
+            log,
+            catalogs.clone(),
+            platform(Some("4.14.10"), Some("4.16.2")),
+        );
+        assert_eq!(
+            res.unwrap().channels,
+            vec![
+                channel("eus-4.14", Some("4.14.10"), None),
+                channel("eus-4.15", None, None),
+                channel("eus-4.16", None, Some("4.16.2")),
+            ]
+        );
+
+        // channels outside of the range are dropped
+        let res = build_platform(
+            log,
+            catalogs.clone(),
+            platform(Some("4.15.1"), Some("4.15.3")),
+        );
+        assert_eq!(
+            res.unwrap().channels,
+            vec![channel("eus-4.15", Some("4.15.1"), Some("4.15.3"))]
+        );
+
+        // no version in range skips the platform section
+        assert!(build_platform(log, catalogs.clone(), platform(Some("4.17.0"), None)).is_none());
+    }
+
+    #[test]
+    fn list_channel_info_pass() {
+        let log = &Logging {
+            log_level: Level::INFO,
+        };
+        let deprecations = OlmDeprecations::default();
+
+        // versions style, the default channel from the fromVersion
+        let info = list_channel_info(
+            log,
+            dc_map(),
+            filter(None, Some("1.1.0")),
+            "versions".to_string(),
+            &deprecations,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            info.path,
+            vec!["test-operator.v1.1.0", "test-operator.v1.2.0"]
+        );
+        assert_eq!(info.package.min_version, Some("1.1.0".to_string()));
+        assert_eq!(info.package.max_version, Some("1.2.0".to_string()));
+        assert_eq!(info.package.channels.len(), 0);
+        assert_eq!(info.package.default_channel, None);
+        assert_eq!(info.channel_paths.len(), 2);
+
+        // channels style, the filter channel is selected and overrides the default channel
+        let info = list_channel_info(
+            log,
+            dc_map(),
+            filter(Some("fast"), None),
+            "channels".to_string(),
+            &deprecations,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            info.path,
+            vec!["test-operator.v1.3.0", "test-operator.v2.0.0"]
+        );
+        assert_eq!(info.package.channels.len(), 1);
+        assert_eq!(info.package.channels[0].name, "fast");
+        assert_eq!(
+            info.package.channels[0].min_version,
+            Some("1.3.0".to_string())
+        );
+        assert_eq!(
+            info.package.channels[0].max_version,
+            Some("2.0.0".to_string())
+        );
+        assert_eq!(info.package.default_channel, Some("fast".to_string()));
+        assert_eq!(info.package.min_version, None);
+
+        // channels style on the default channel, no override
+        let info = list_channel_info(
+            log,
+            dc_map(),
+            filter(Some("stable"), None),
+            "channels".to_string(),
+            &deprecations,
+            false,
+        )
+        .unwrap();
+        assert_eq!(info.package.channels[0].name, "stable");
+        assert_eq!(info.package.default_channel, None);
+
+        // a channel the package doesn't have selects nothing
+        let info = list_channel_info(
+            log,
+            dc_map(),
+            filter(Some("candidate"), None),
+            "channels".to_string(),
+            &deprecations,
+            false,
+        )
+        .unwrap();
+        assert_eq!(info.path.len(), 0);
+        assert_eq!(info.package.channels.len(), 0);
+
+        // deprecated bundles are marked, and left out of the path when excluded
+        let deprecations: OlmDeprecations = serde_json::from_value(json!({
+            "schema": "olm.deprecations", "package": "test-operator",
+            "entries": [
+                { "reference": { "schema": "olm.bundle", "name": "test-operator.v1.1.0" },
+                  "message": "use 1.2.0" },
+                { "reference": { "schema": "olm.bundle", "name": "test-operator.v0.9.0" },
+                  "message": "not in the catalog" }
+            ] }))
+        .unwrap();
+        let info = list_channel_info(
+            log,
+            dc_map(),
+            filter(None, None),
+            "bundles".to_string(),
+            &deprecations,
+            false,
+        )
+        .unwrap();
+        assert_eq!(info.deprecated, vec!["test-operator.v1.1.0"]);
+        assert_eq!(info.path.len(), 2);
+        let info = list_channel_info(
+            log,
+            dc_map(),
+            filter(None, None),
+            "bundles".to_string(),
+            &deprecations,
+            true,
+        )
+        .unwrap();
+        assert_eq!(info.deprecated, vec!["test-operator.v1.1.0"]);
+        assert_eq!(info.path, vec!["test-operator.v1.2.0"]);
+        assert_eq!(info.package.bundles.len(), 1);
+        // deprecations of other bundles don't affect the path
+        let info = list_channel_info(
+            log,
+            dc_map(),
+            filter(Some("fast"), None),
+            "channels".to_string(),
+            &deprecations,
+            true,
+        )
+        .unwrap();
+        assert_eq!(info.deprecated.len(), 0);
+        assert_eq!(info.path.len(), 2);
+
+        // bundles style lists every bundle of the path
+        let info = list_channel_info(
+            log,
+            dc_map(),
+            filter(None, None),
+            "bundles".to_string(),
+            &deprecations,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            info.path,
+            vec!["test-operator.v1.1.0", "test-operator.v1.2.0"]
+        );
+        assert_eq!(info.package.bundles.len(), 2);
+        assert_eq!(info.package.min_version, None);
+
+        // invalid catalogs and filters are reported, not panicking
+        let mut no_default = dc_map();
+        no_default
+            .get_mut("olm.package=test-operator")
+            .unwrap()
+            .default_channel = None;
+        let err = list_channel_info(
+            log,
+            no_default,
+            filter(None, None),
+            "bundles".to_string(),
+            &deprecations,
+            false,
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("has no defaultChannel"));
+        let err = list_channel_info(
+            log,
+            dc_map(),
+            filter(None, Some("1.1")),
+            "versions".to_string(),
+            &deprecations,
+            false,
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("fromVersion 1.1"));
+    }
 }
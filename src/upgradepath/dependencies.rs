@@ -0,0 +1,653 @@
+use crate::fbc::reader::*;
+use crate::isc::generate::*;
+use custom_logger::*;
+use mirror_error::MirrorError;
+use semver::{Prerelease, Version, VersionReq};
+use serde_json::Value;
+use std::collections::HashMap;
+
+// dependencies pulled into a catalog by the selected bundles
+#[derive(Debug, Clone, Default)]
+pub struct DependencyReport {
+    pub catalog: String,
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    // the bundle declaring the requirement
+    pub required_by: String,
+    // i.e olm.package.required etcd >=0.9.0
+    pub requirement: String,
+    // the providing package and bundle (none when unresolved)
+    pub package: Option<String>,
+    pub bundle: Option<String>,
+}
+
+impl DependencyReport {
+    pub fn display(&self, log: &Logging) {
+        if self.dependencies.is_empty() {
+            return;
+        }
+        log.mid(&format!("  dependencies for catalog {}", self.catalog));
+        for d in self.dependencies.iter() {
+            match (&d.package, &d.bundle) {
+                (Some(p), Some(b)) => log.mid(&format!(
+                    "    {} ({}) pulled in by {} [{}]",
+                    p, b, d.required_by, d.requirement
+                )),
+                _ => log.warn(&format!(
+                    "    unresolved {} required by {}",
+                    d.requirement, d.required_by
+                )),
+            }
+        }
+    }
+
+    pub fn unresolved(&self) -> usize {
+        self.dependencies
+            .iter()
+            .filter(|d| d.bundle.is_none())
+            .count()
+    }
+}
+
+// package blobs read from the catalog configs dir (read once per package)
+struct CatalogPackages {
+    config_dir: String,
+    bundles: HashMap<String, Vec<OlmBundle>>,
+    heads: HashMap<String, String>,
+    channels: HashMap<String, (String, Vec<OlmChannel>)>,
+    names: Option<Vec<String>>,
+}
+
+impl CatalogPackages {
+    fn load(&mut self, name: &str) -> Vec<OlmBundle> {
+        if !self.bundles.contains_key(name) {
+            let blobs =
+                read_package_blobs(self.config_dir.clone(), name.to_string()).unwrap_or(vec![]);
+            let channels = get_channels(&blobs);
+            if let Some(pkg) = get_package(&blobs) {
+                let dc = pkg.default_channel.unwrap_or_default();
+                let head = channels
+                    .iter()
+                    .find(|c| c.name == dc)
+                    .and_then(|c| c.head());
+                if let Some(h) = head {
+                    self.heads.insert(name.to_string(), h);
+                }
+                self.channels.insert(name.to_string(), (dc, channels));
+            }
+            self.bundles.insert(name.to_string(), get_bundles(&blobs));
+        }
+        self.bundles.get(name).unwrap().clone()
+    }
+
+    // the packages (sorted by name) with bundles providing the gvk, only the packages
+    // mentioning the group and kind are parsed
+    fn gvk_providers(&mut self, gvk: &Value) -> Vec<String> {
+        if self.names.is_none() {
+            self.names = Some(list_packages(self.config_dir.clone()));
+        }
+        let group = as_string(&gvk["group"]);
+        let kind = as_string(&gvk["kind"]);
+        let mut providers = vec![];
+        for name in self.names.clone().unwrap().iter() {
+            if !self.bundles.contains_key(name)
+                && !package_contains(
+                    self.config_dir.clone(),
+                    name.clone(),
+                    &[group.as_str(), kind.as_str()],
+                )
+            {
+                continue;
+            }
+            if self.load(name).iter().any(|b| provides_gvk(b, gvk)) {
+                providers.push(name.clone());
+            }
+        }
+        providers
+    }
+
+    // the channel holding the bundle (the default channel first) and the default channel
+    fn bundle_channel(&self, bundle: &OlmBundle) -> Option<(String, String)> {
+        let (dc, channels) = self.channels.get(&bundle.package)?;
+        let mut holding: Vec<&OlmChannel> = channels
+            .iter()
+            .filter(|c| c.entries.iter().any(|e| e.name == bundle.name))
+            .collect();
+        holding.sort_by_key(|c| (&c.name != dc, c.name.clone()));
+        holding.first().map(|c| (c.name.clone(), dc.clone()))
+    }
+}
+
+// resolve olm.package.required and olm.gvk.required properties of the selected bundles
+// against the same catalog, providing packages are appended to packages
+pub fn resolve_dependencies(
+    log: &Logging,
+    catalog: String,
+    config_dir: String,
+    packages: &mut Vec<Package>,
+    isc_style: String,
+) -> Result<DependencyReport, MirrorError> {
+    let mut report = DependencyReport {
+        catalog,
+        dependencies: vec![],
+    };
+    let mut cp = CatalogPackages {
+        config_dir,
+        bundles: HashMap::new(),
+        heads: HashMap::new(),
+        channels: HashMap::new(),
+        names: None,
+    };
+
+    // bundles already part of the imagesetconfig
+    let mut selected: Vec<OlmBundle> = vec![];
+    for pkg in packages.iter() {
+        let bundles = cp.load(&pkg.name);
+        let head = cp.heads.get(&pkg.name).cloned();
+        selected.extend(selected_bundles(pkg, &bundles, head));
+    }
+    let mut queue: Vec<OlmBundle> = selected.clone();
+    let mut visited: Vec<String> = vec![];
+
+    while let Some(bundle) = queue.pop() {
+        if visited.contains(&bundle.name) {
+            continue;
+        }
+        visited.push(bundle.name.clone());
+
+        for req in bundle.get_properties("olm.package.required").iter() {
+            let name = as_string(&req["packageName"]);
+            let range = as_string(&req["versionRange"]);
+            let requirement = format!("olm.package.required {} {}", name, range)
+                .trim()
+                .to_string();
+            // a selected bundle of the package already satisfies the range
+            if selected
+                .iter()
+                .any(|b| b.package == name && in_range(b, &range))
+            {
+                log.debug(&format!(
+                    "[resolve_dependencies] {} satisfied by a selected bundle",
+                    requirement
+                ));
+                continue;
+            }
+            let candidates: Vec<OlmBundle> = cp
+                .load(&name)
+                .into_iter()
+                .filter(|b| in_range(b, &range))
+                .collect();
+            let provider = highest_bundle(&candidates);
+            add_dependency(
+                &mut report,
+                &mut selected,
+                &mut queue,
+                packages,
+                &cp,
+                &bundle.name,
+                requirement,
+                provider,
+                &isc_style,
+            );
+        }
+
+        for req in bundle.get_properties("olm.gvk.required").iter() {
+            let requirement = format!(
+                "olm.gvk.required {}/{} {}",
+                as_string(&req["group"]),
+                as_string(&req["version"]),
+                as_string(&req["kind"])
+            );
+            if selected.iter().any(|b| provides_gvk(b, req)) {
+                log.debug(&format!(
+                    "[resolve_dependencies] {} satisfied by a selected bundle",
+                    requirement
+                ));
+                continue;
+            }
+            let mut provider: Option<OlmBundle> = None;
+            if let Some(name) = cp.gvk_providers(req).first() {
+                let providing: Vec<OlmBundle> = cp
+                    .load(name)
+                    .into_iter()
+                    .filter(|b| provides_gvk(b, req))
+                    .collect();
+                // prefer the head of the default channel
+                let head = cp.heads.get(name).cloned().unwrap_or_default();
+                provider = match providing.iter().find(|b| b.name == head) {
+                    Some(b) => Some(b.clone()),
+                    None => highest_bundle(&providing),
+                };
+            }
+            add_dependency(
+                &mut report,
+                &mut selected,
+                &mut queue,
+                packages,
+                &cp,
+                &bundle.name,
+                requirement,
+                provider,
+                &isc_style,
+            );
+        }
+    }
+    Ok(report)
+}
+
+// record the dependency and add the providing bundle to the imagesetconfig packages
+#[allow(clippy::too_many_arguments)]
+fn add_dependency(
+    report: &mut DependencyReport,
+    selected: &mut Vec<OlmBundle>,
+    queue: &mut Vec<OlmBundle>,
+    packages: &mut Vec<Package>,
+    cp: &CatalogPackages,
+    required_by: &str,
+    requirement: String,
+    provider: Option<OlmBundle>,
+    isc_style: &str,
+) {
+    let mut dependency = Dependency {
+        required_by: required_by.to_string(),
+        requirement,
+        package: None,
+        bundle: None,
+    };
+    if let Some(p) = provider.as_ref() {
+        dependency.package = Some(p.package.clone());
+        dependency.bundle = Some(p.name.clone());
+    }
+    if !report.dependencies.contains(&dependency) {
+        report.dependencies.push(dependency);
+    }
+    if provider.is_none() {
+        return;
+    }
+    let provider = provider.unwrap();
+    if selected.iter().any(|b| b.name == provider.name) {
+        return;
+    }
+
+    let pos = packages.iter().position(|p| p.name == provider.package);
+    let new_package = pos.is_none();
+    let idx = match pos {
+        Some(i) => i,
+        None => {
+            packages.push(Package {
+                name: provider.package.clone(),
+                ..Default::default()
+            });
+            packages.len() - 1
+        }
+    };
+    let pkg = &mut packages[idx];
+    let version = provider.version().unwrap_or_default();
+    if isc_style == "bundles" {
+        pkg.bundles.push(Bundle {
+            name: provider.name.clone(),
+        });
+    } else if isc_style == "channels" || pkg.channels.len() > 0 {
+        // package level versions can't be combined with channels, the range is set
+        // on the channel holding the bundle (every bundle of a valid catalog has one)
+        let channel = cp.bundle_channel(&provider);
+        if channel.is_none() {
+            return;
+        }
+        let (channel, default_channel) = channel.unwrap();
+        let pos = pkg.channels.iter().position(|c| c.name == channel);
+        let new_channel = pos.is_none();
+        let ch = match pos {
+            Some(i) => &mut pkg.channels[i],
+            None => {
+                pkg.channels.push(IscChannel {
+                    name: channel.clone(),
+                    min_version: None,
+                    max_version: None,
+                });
+                pkg.channels.last_mut().unwrap()
+            }
+        };
+        extend_range(
+            &mut ch.min_version,
+            &mut ch.max_version,
+            &version,
+            new_channel,
+        );
+        // the default channel must be overridden when it is not mirrored
+        if pkg.default_channel.is_none() && !pkg.channels.iter().any(|c| c.name == default_channel)
+        {
+            pkg.default_channel = Some(channel);
+        }
+    } else {
+        extend_range(
+            &mut pkg.min_version,
+            &mut pkg.max_version,
+            &version,
+            new_package,
+        );
+    }
+    selected.push(provider.clone());
+    queue.push(provider);
+}
+
+// widen the min/max versions to include the version, a missing bound is open ended
+// (an existing entry without versions is the channel head, kept by only setting the min)
+fn extend_range(min: &mut Option<String>, max: &mut Option<String>, version: &str, new: bool) {
+    if min.is_none() && max.is_none() {
+        *min = Some(version.to_string());
+        if new {
+            *max = Some(version.to_string());
+        }
+        return;
+    }
+    if min
+        .as_ref()
+        .is_some_and(|m| compare_versions(version, m).is_lt())
+    {
+        *min = Some(version.to_string());
+    }
+    if max
+        .as_ref()
+        .is_some_and(|m| compare_versions(version, m).is_gt())
+    {
+        *max = Some(version.to_string());
+    }
+}
+
+// the head of the channel an imagesetconfig package mirrors when it has no other selection
+// (its defaultChannel, or the catalog default channel)
+pub fn selected_head(pkg: &Package, blobs: &Vec<Value>) -> Option<String> {
+    let channel = pkg
+        .default_channel
+        .clone()
+        .or(get_package(blobs).and_then(|p| p.default_channel))?;
+    get_channels(blobs)
+        .iter()
+        .find(|c| c.name == channel)
+        .and_then(|c| c.head())
+}
+
+// the bundles of a package selected for the imagesetconfig (bundles, min/max versions or the channel head)
+pub fn selected_bundles(
+    pkg: &Package,
+    bundles: &[OlmBundle],
+    head: Option<String>,
+) -> Vec<OlmBundle> {
+    if pkg.bundles.len() > 0 {
+        return bundles
+            .iter()
+            .filter(|b| pkg.bundles.iter().any(|x| x.name == b.name))
+            .cloned()
+            .collect();
+    }
+    let mut ranges: Vec<(Option<String>, Option<String>)> = vec![];
+    if pkg.min_version.is_some() || pkg.max_version.is_some() {
+        ranges.push((pkg.min_version.clone(), pkg.max_version.clone()));
+    }
+    for ch in pkg.channels.iter() {
+        ranges.push((ch.min_version.clone(), ch.max_version.clone()));
+    }
+    if ranges.len() == 0 {
+        return bundles
+            .iter()
+            .filter(|b| Some(b.name.clone()) == head)
+            .cloned()
+            .collect();
+    }
+    bundles
+        .iter()
+        .filter(|b| {
+            let v = b.version().unwrap_or_default();
+            ranges.iter().any(|(min, max)| {
+                min.as_ref().is_none_or(|m| compare_versions(&v, m).is_ge())
+                    && max.as_ref().is_none_or(|m| compare_versions(&v, m).is_le())
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+// check the bundle version against an olm version range (i.e ">=1.0.0 <2.0.0 || 3.x")
+pub fn in_range(bundle: &OlmBundle, range: &str) -> bool {
+    if range.trim().len() == 0 {
+        return true;
+    }
+    let v = Version::parse(&bundle.version().unwrap_or_default());
+    if v.is_err() {
+        return false;
+    }
+    let v = v.unwrap();
+    // olm bundle versions often carry a release suffix (i.e 1.51.0-1), which semver
+    // treats as a pre-release excluded from most ranges
+    let mut stripped = v.clone();
+    stripped.pre = Prerelease::EMPTY;
+    for part in range.split("||") {
+        let req = VersionReq::parse(&part.split_whitespace().collect::<Vec<&str>>().join(", "));
+        if req.is_ok() {
+            let req = req.unwrap();
+            if req.matches(&v) || req.matches(&stripped) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+fn highest_bundle(bundles: &[OlmBundle]) -> Option<OlmBundle> {
+    bundles
+        .iter()
+        .max_by(|a, b| {
+            compare_versions(
+                &a.version().unwrap_or_default(),
+                &b.version().unwrap_or_default(),
+            )
+        })
+        .cloned()
+}
+
+pub fn provides_gvk(bundle: &OlmBundle, gvk: &Value) -> bool {
+    bundle.get_properties("olm.gvk").iter().any(|p| {
+        p["group"] == gvk["group"] && p["version"] == gvk["version"] && p["kind"] == gvk["kind"]
+    })
+}
+
+fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    match (Version::parse(a), Version::parse(b)) {
+        (Ok(x), Ok(y)) => x.cmp(&y),
+        _ => a.cmp(b),
+    }
+}
+
+fn as_string(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+    use std::fs;
+
+    fn bundle(package: &str, version: &str, extra: &str) -> String {
+        format!(
+            "{{ \"schema\": \"olm.bundle\", \"name\": \"{p}.v{v}\", \"package\": \"{p}\",
+              \"properties\": [ {{ \"type\": \"olm.package\", \"value\": {{ \"packageName\": \"{p}\", \"version\": \"{v}\" }} }} {e} ] }}",
+            p = package,
+            v = version,
+            e = extra
+        )
+    }
+
+    // the head is the first entry, the others are skipped by it
+    fn write_package(config_dir: &str, package: &str, entries: &[&str], bundles: Vec<String>) {
+        fs::create_dir_all(format!("{}/{}", config_dir, package))
+            .expect("should create package folder");
+        let skips: Vec<String> = entries[1..].iter().map(|e| format!("\"{}\"", e)).collect();
+        let mut channel_entries = vec![format!(
+            "{{ \"name\": \"{}\", \"skips\": [ {} ] }}",
+            entries[0],
+            skips.join(", ")
+        )];
+        for e in entries[1..].iter() {
+            channel_entries.push(format!("{{ \"name\": \"{}\" }}", e));
+        }
+        let data = format!(
+            "{{ \"schema\": \"olm.package\", \"name\": \"{p}\", \"defaultChannel\": \"stable\" }}
+            {{ \"schema\": \"olm.channel\", \"name\": \"stable\", \"package\": \"{p}\", \"entries\": [ {e} ] }}
+            {b}",
+            p = package,
+            e = channel_entries.join(", "),
+            b = bundles.join("\n")
+        );
+        fs::write(format!("{}/{}/catalog.json", config_dir, package), data)
+            .expect("should write catalog");
+    }
+
+    #[test]
+    fn resolve_dependencies_pass() {
+        let log = &Logging {
+            log_level: Level::INFO,
+        };
+        let config_dir = "./test-artifacts/dependencies/configs";
+        // the requirement is declared twice, it is only reported once
+        write_package(
+            config_dir,
+            "app-operator",
+            &["app-operator.v1.0.0"],
+            vec![bundle(
+                "app-operator",
+                "1.0.0",
+                ", { \"type\": \"olm.package.required\", \"value\": { \"packageName\": \"db-operator\", \"versionRange\": \">=2.0.0 <3.0.0\" } }
+                 , { \"type\": \"olm.package.required\", \"value\": { \"packageName\": \"db-operator\", \"versionRange\": \">=2.0.0 <3.0.0\" } }",
+            )],
+        );
+        write_package(
+            config_dir,
+            "db-operator",
+            &["db-operator.v3.0.0", "db-operator.v2.1.0-1"],
+            vec![
+                bundle(
+                    "db-operator",
+                    "2.1.0-1",
+                    ", { \"type\": \"olm.gvk.required\", \"value\": { \"group\": \"cache.io\", \"version\": \"v1\", \"kind\": \"Cache\" } }",
+                ),
+                bundle("db-operator", "3.0.0", ""),
+            ],
+        );
+        write_package(
+            config_dir,
+            "cache-operator",
+            &["cache-operator.v0.2.0", "cache-operator.v0.1.0"],
+            vec![
+                bundle(
+                    "cache-operator",
+                    "0.1.0",
+                    ", { \"type\": \"olm.gvk\", \"value\": { \"group\": \"cache.io\", \"version\": \"v1\", \"kind\": \"Cache\" } }",
+                ),
+                bundle(
+                    "cache-operator",
+                    "0.2.0",
+                    ", { \"type\": \"olm.gvk\", \"value\": { \"group\": \"cache.io\", \"version\": \"v1\", \"kind\": \"Cache\" } }",
+                ),
+            ],
+        );
+
+        let mut packages = vec![Package {
+            name: "app-operator".to_string(),
+            bundles: vec![Bundle {
+                name: "app-operator.v1.0.0".to_string(),
+            }],
+            ..Default::default()
+        }];
+        let report = resolve_dependencies(
+            log,
+            "test-index:v0.0.1".to_string(),
+            config_dir.to_string(),
+            &mut packages,
+            "bundles".to_string(),
+        )
+        .expect("should resolve dependencies");
+        assert_eq!(packages.len(), 3);
+        assert_eq!(packages[1].name, "db-operator");
+        assert_eq!(packages[1].bundles[0].name, "db-operator.v2.1.0-1");
+        assert_eq!(packages[2].name, "cache-operator");
+        assert_eq!(packages[2].bundles[0].name, "cache-operator.v0.2.0");
+        assert_eq!(report.dependencies.len(), 2);
+        assert_eq!(report.dependencies[0].required_by, "app-operator.v1.0.0");
+        assert_eq!(report.dependencies[1].required_by, "db-operator.v2.1.0-1");
+        assert_eq!(report.unresolved(), 0);
+
+        // channels style, the range is set on the channel holding the providing bundle
+        let mut packages = vec![Package {
+            name: "app-operator".to_string(),
+            channels: vec![IscChannel {
+                name: "stable".to_string(),
+                min_version: Some("1.0.0".to_string()),
+                max_version: Some("1.0.0".to_string()),
+            }],
+            ..Default::default()
+        }];
+        resolve_dependencies(
+            log,
+            "test-index:v0.0.1".to_string(),
+            config_dir.to_string(),
+            &mut packages,
+            "channels".to_string(),
+        )
+        .expect("should resolve dependencies");
+        assert_eq!(packages.len(), 3);
+        assert_eq!(
+            packages[1].channels,
+            vec![IscChannel {
+                name: "stable".to_string(),
+                min_version: Some("2.1.0-1".to_string()),
+                max_version: Some("2.1.0-1".to_string()),
+            }]
+        );
+        assert_eq!(packages[1].min_version, None);
+        assert_eq!(packages[1].default_channel, None);
+
+        // versions style, a package that already has channels keeps the range on the channel
+        let mut packages = vec![
+            Package {
+                name: "app-operator".to_string(),
+                min_version: Some("1.0.0".to_string()),
+                max_version: Some("1.0.0".to_string()),
+                ..Default::default()
+            },
+            Package {
+                name: "db-operator".to_string(),
+                channels: vec![IscChannel {
+                    name: "stable".to_string(),
+                    min_version: Some("3.0.0".to_string()),
+                    max_version: None,
+                }],
+                ..Default::default()
+            },
+        ];
+        resolve_dependencies(
+            log,
+            "test-index:v0.0.1".to_string(),
+            config_dir.to_string(),
+            &mut packages,
+            "versions".to_string(),
+        )
+        .expect("should resolve dependencies");
+        assert_eq!(packages[1].min_version, None);
+        assert_eq!(
+            packages[1].channels[0].min_version,
+            Some("2.1.0-1".to_string())
+        );
+        // open ended up to the channel head
+        assert_eq!(packages[1].channels[0].max_version, None);
+        assert_eq!(packages[2].name, "cache-operator");
+        assert_eq!(packages[2].min_version, Some("0.2.0".to_string()));
+
+        fs::remove_dir_all("./test-artifacts/dependencies")
+            .expect("should delete dependencies test folder");
+    }
+}
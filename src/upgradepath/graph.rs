@@ -0,0 +1,296 @@
+use crate::fbc::reader::*;
+use crate::upgradepath::dependencies::in_range;
+use mirror_error::MirrorError;
+use std::fs;
+
+pub const GRAPH_FORMATS: [&str; 2] = ["dot", "mermaid"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EdgeKind {
+    Replaces,
+    Skips,
+    SkipRange,
+}
+
+impl EdgeKind {
+    fn label(&self) -> &str {
+        match self {
+            EdgeKind::Replaces => "replaces",
+            EdgeKind::Skips => "skips",
+            EdgeKind::SkipRange => "skipRange",
+        }
+    }
+}
+
+// an upgrade edge, from the older bundle to the bundle replacing (or skipping) it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub kind: EdgeKind,
+}
+
+// the full upgrade graph of a channel
+#[derive(Debug, Clone, Default)]
+pub struct ChannelGraph {
+    pub package: String,
+    pub channel: String,
+    pub nodes: Vec<String>,
+    pub edges: Vec<Edge>,
+    pub head: Option<String>,
+    pub from: Option<String>,
+    pub path: Vec<String>,
+}
+
+impl ChannelGraph {
+    // build the graph from the channel entries, bundles are used to resolve the skipRange versions
+    pub fn new(
+        package: String,
+        channel: &OlmChannel,
+        bundles: &Vec<OlmBundle>,
+        from: Option<String>,
+        path: Vec<String>,
+    ) -> Self {
+        let mut graph = ChannelGraph {
+            package,
+            channel: channel.name.clone(),
+            nodes: channel.entries.iter().map(|e| e.name.clone()).collect(),
+            edges: vec![],
+            head: channel.head(),
+            from,
+            path,
+        };
+        for e in channel.entries.iter() {
+            if let Some(r) = &e.replaces {
+                graph.push_edge(r, &e.name, EdgeKind::Replaces);
+            }
+            for s in e.skips.clone().unwrap_or(vec![]).iter() {
+                graph.push_edge(s, &e.name, EdgeKind::Skips);
+            }
+            if let Some(range) = &e.skip_range {
+                for b in bundles.iter() {
+                    if b.name != e.name && graph.nodes.contains(&b.name) && in_range(b, range) {
+                        graph.push_edge(&b.name, &e.name, EdgeKind::SkipRange);
+                    }
+                }
+            }
+        }
+        graph
+    }
+
+    fn push_edge(&mut self, from: &String, to: &String, kind: EdgeKind) {
+        // replaced or skipped bundles that are not channel entries are still drawn
+        if !self.nodes.contains(from) {
+            self.nodes.push(from.clone());
+        }
+        let edge = Edge {
+            from: from.clone(),
+            to: to.clone(),
+            kind,
+        };
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+
+    // an edge between two consecutive bundles of the computed path
+    fn on_path(&self, edge: &Edge) -> bool {
+        let from = self.path.iter().position(|p| p == &edge.from);
+        let to = self.path.iter().position(|p| p == &edge.to);
+        match (from, to) {
+            (Some(f), Some(t)) => t == f + 1,
+            // the path starts after the from version
+            (None, Some(0)) => self.from.as_ref() == Some(&edge.from),
+            _ => false,
+        }
+    }
+
+    fn node_label(&self, name: &String) -> String {
+        let mut tags = vec![];
+        if self.head.as_ref() == Some(name) {
+            tags.push("head");
+        }
+        if self.from.as_ref() == Some(name) {
+            tags.push("from");
+        }
+        if tags.len() == 0 {
+            return name.clone();
+        }
+        format!("{} ({})", name, tags.join(", "))
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out = format!(
+            "digraph \"{}/{}\" {{\n  rankdir=LR;\n  node [shape=box, style=rounded];\n",
+            self.package, self.channel
+        );
+        for n in self.nodes.iter() {
+            let mut attrs = format!("label=\"{}\"", self.node_label(n));
+            if self.from.as_ref() == Some(n) {
+                attrs.push_str(", style=\"rounded,filled\", fillcolor=\"orange\"");
+            } else if self.head.as_ref() == Some(n) {
+                attrs.push_str(", style=\"rounded,filled\", fillcolor=\"palegreen\"");
+            } else if self.path.contains(n) {
+                attrs.push_str(", style=\"rounded,filled\", fillcolor=\"lightblue\"");
+            }
+            if self.path.contains(n) {
+                attrs.push_str(", penwidth=2");
+            }
+            out.push_str(&format!("  \"{}\" [{}];\n", n, attrs));
+        }
+        for e in self.edges.iter() {
+            let mut attrs = format!("label=\"{}\"", e.kind.label());
+            match e.kind {
+                EdgeKind::Skips => attrs.push_str(", style=dashed"),
+                EdgeKind::SkipRange => attrs.push_str(", style=dotted"),
+                EdgeKind::Replaces => {}
+            }
+            if self.on_path(e) {
+                attrs.push_str(", color=\"blue\", penwidth=2");
+            }
+            out.push_str(&format!("  \"{}\" -> \"{}\" [{}];\n", e.from, e.to, attrs));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        // mermaid node ids can't contain dots, the bundle name is used as label
+        let id = |name: &String| format!("n{}", self.nodes.iter().position(|n| n == name).unwrap());
+        let mut out = format!(
+            "---\ntitle: {}/{}\n---\ngraph LR\n",
+            self.package, self.channel
+        );
+        for n in self.nodes.iter() {
+            out.push_str(&format!("  {}[\"{}\"]\n", id(n), self.node_label(n)));
+        }
+        let mut path_edges = vec![];
+        for (i, e) in self.edges.iter().enumerate() {
+            let arrow = match e.kind {
+                EdgeKind::Replaces => "-->",
+                _ => "-.->",
+            };
+            out.push_str(&format!(
+                "  {} {}|{}| {}\n",
+                id(&e.from),
+                arrow,
+                e.kind.label(),
+                id(&e.to)
+            ));
+            if self.on_path(e) {
+                path_edges.push(i.to_string());
+            }
+        }
+        out.push_str("  classDef path fill:#add8e6,stroke-width:2px\n");
+        out.push_str("  classDef head fill:#98fb98\n");
+        out.push_str("  classDef from fill:#ffa500\n");
+        // the last class statement wins, from > head > path
+        for n in self.nodes.iter() {
+            if self.path.contains(n) {
+                out.push_str(&format!("  class {} path\n", id(n)));
+            }
+        }
+        if let Some(h) = &self.head {
+            out.push_str(&format!("  class {} head\n", id(h)));
+        }
+        if let Some(f) = self.from.as_ref().filter(|f| self.nodes.contains(f)) {
+            out.push_str(&format!("  class {} from\n", id(f)));
+        }
+        if path_edges.len() > 0 {
+            out.push_str(&format!(
+                "  linkStyle {} stroke:#0000ff,stroke-width:2px\n",
+                path_edges.join(",")
+            ));
+        }
+        out
+    }
+}
+
+// write the graph to <dir>/<package>-<channel>.(dot|mmd)
+pub fn write_graph(
+    dir: &String,
+    graph: &ChannelGraph,
+    format: &str,
+) -> Result<String, MirrorError> {
+    let res = fs::create_dir_all(dir);
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[write_graph] creating directory {} {}",
+            dir,
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    let (contents, ext) = match format {
+        "mermaid" => (graph.to_mermaid(), "mmd"),
+        _ => (graph.to_dot(), "dot"),
+    };
+    let file = format!("{}/{}-{}.{}", dir, graph.package, graph.channel, ext);
+    let res = fs::write(&file, contents);
+    if res.is_err() {
+        return Err(MirrorError::new(&format!(
+            "[write_graph] writing {} {}",
+            file,
+            res.err().unwrap().to_string().to_lowercase()
+        )));
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+
+    #[test]
+    fn channel_graph_pass() {
+        let blobs: Vec<serde_json::Value> = serde_json::from_str(
+            "[
+            { \"schema\": \"olm.channel\", \"name\": \"stable\", \"package\": \"test-operator\",
+              \"entries\": [
+                { \"name\": \"test-operator.v0.0.1\" },
+                { \"name\": \"test-operator.v0.0.2\", \"replaces\": \"test-operator.v0.0.1\" },
+                { \"name\": \"test-operator.v0.0.3\", \"replaces\": \"test-operator.v0.0.2\", \"skips\": [ \"test-operator.v0.0.1\" ] },
+                { \"name\": \"test-operator.v0.1.0\", \"replaces\": \"test-operator.v0.0.3\", \"skipRange\": \">=0.0.1 <0.1.0\" } ] },
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.1\", \"package\": \"test-operator\",
+              \"properties\": [ { \"type\": \"olm.package\", \"value\": { \"packageName\": \"test-operator\", \"version\": \"0.0.1\" } } ] },
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.2\", \"package\": \"test-operator\",
+              \"properties\": [ { \"type\": \"olm.package\", \"value\": { \"packageName\": \"test-operator\", \"version\": \"0.0.2\" } } ] },
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.3\", \"package\": \"test-operator\",
+              \"properties\": [ { \"type\": \"olm.package\", \"value\": { \"packageName\": \"test-operator\", \"version\": \"0.0.3\" } } ] },
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.1.0\", \"package\": \"test-operator\",
+              \"properties\": [ { \"type\": \"olm.package\", \"value\": { \"packageName\": \"test-operator\", \"version\": \"0.1.0\" } } ] }
+            ]",
+        )
+        .expect("should parse blobs");
+        let channels = get_channels(&blobs);
+        let graph = ChannelGraph::new(
+            "test-operator".to_string(),
+            &channels[0],
+            &get_bundles(&blobs),
+            Some("test-operator.v0.0.1".to_string()),
+            vec!["test-operator.v0.1.0".to_string()],
+        );
+        assert_eq!(graph.nodes.len(), 4);
+        // 3 replaces, 1 skips and 3 skipRange edges
+        assert_eq!(graph.edges.len(), 7);
+        assert_eq!(graph.head, Some("test-operator.v0.1.0".to_string()));
+
+        let dot = graph.to_dot();
+        assert!(dot.contains("digraph \"test-operator/stable\""));
+        assert!(dot.contains("label=\"test-operator.v0.1.0 (head)\""));
+        assert!(dot.contains(
+            "\"test-operator.v0.0.1\" -> \"test-operator.v0.1.0\" [label=\"skipRange\", style=dotted, color=\"blue\", penwidth=2];"
+        ));
+
+        let mermaid = graph.to_mermaid();
+        assert!(mermaid.contains("graph LR"));
+        assert!(mermaid.contains("n0 -->|replaces| n1"));
+        assert!(mermaid.contains("class n3 head"));
+        assert!(mermaid.contains("class n0 from"));
+
+        let dir = "./test-artifacts/graph".to_string();
+        let file = write_graph(&dir, &graph, "mermaid").expect("should write graph");
+        assert_eq!(file, "./test-artifacts/graph/test-operator-stable.mmd");
+        fs::remove_dir_all(&dir).expect("should delete graph test folder");
+    }
+}
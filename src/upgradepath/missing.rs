@@ -0,0 +1,120 @@
+use custom_logger::*;
+
+// packages requested in the filter but not found in a catalog
+#[derive(Debug, Clone, Default)]
+pub struct MissingReport {
+    pub catalog: String,
+    pub missing: Vec<MissingPackage>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingPackage {
+    pub name: String,
+    pub suggestion: Option<String>,
+}
+
+impl MissingReport {
+    // build the report for a catalog, available are the package names found under 'configs'
+    pub fn new(catalog: String, missing: Vec<String>, available: &Vec<String>) -> Self {
+        let mut report = MissingReport {
+            catalog,
+            missing: vec![],
+        };
+        for name in missing.iter() {
+            report.missing.push(MissingPackage {
+                name: name.clone(),
+                suggestion: suggest(name, available),
+            });
+        }
+        report
+    }
+
+    pub fn display(&self, log: &Logging) {
+        if self.missing.len() == 0 {
+            return;
+        }
+        log.warn(&format!(
+            "catalog {} : {} requested package(s) not found",
+            self.catalog,
+            self.missing.len()
+        ));
+        for m in self.missing.iter() {
+            match &m.suggestion {
+                Some(s) => log.warn(&format!("  {} (did you mean '{}' ?)", m.name, s)),
+                None => log.warn(&format!("  {}", m.name)),
+            }
+        }
+    }
+}
+
+// find the closest package name (prefix/substring matches first, then edit distance)
+pub fn suggest(name: &str, available: &Vec<String>) -> Option<String> {
+    let mut best: Option<(usize, String)> = None;
+    for candidate in available.iter() {
+        let distance = if candidate.starts_with(name) || name.starts_with(candidate.as_str()) {
+            0
+        } else {
+            levenshtein(name, candidate)
+        };
+        if best.is_none() || distance < best.as_ref().unwrap().0 {
+            best = Some((distance, candidate.clone()));
+        }
+    }
+    // only suggest reasonably close names
+    let max_distance = std::cmp::max(2, name.len() / 3);
+    match best {
+        Some((d, c)) if d <= max_distance => Some(c),
+        _ => None,
+    }
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            current[j] = std::cmp::min(
+                std::cmp::min(previous[j] + 1, current[j - 1] + 1),
+                previous[j - 1] + cost,
+            );
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+
+    #[test]
+    fn suggest_pass() {
+        let available = vec![
+            "jaeger-product".to_string(),
+            "serverless-operator".to_string(),
+            "amq-streams".to_string(),
+        ];
+        assert_eq!(
+            suggest("jaeger", &available),
+            Some("jaeger-product".to_string())
+        );
+        assert_eq!(
+            suggest("serverles-operator", &available),
+            Some("serverless-operator".to_string())
+        );
+        assert_eq!(suggest("3scale-operator", &available), None);
+        let report = MissingReport::new(
+            "test-index:v1".to_string(),
+            vec!["amq-stream".to_string()],
+            &available,
+        );
+        assert_eq!(
+            report.missing[0].suggestion,
+            Some("amq-streams".to_string())
+        );
+    }
+}
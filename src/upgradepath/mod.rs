@@ -1 +1,5 @@
 pub mod calculate;
+pub mod dependencies;
+pub mod graph;
+pub mod missing;
+pub mod ocp;
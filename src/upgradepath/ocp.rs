@@ -0,0 +1,217 @@
+use crate::fbc::reader::*;
+use custom_logger::*;
+use serde_json::Value;
+
+// bundles on an upgrade path checked against a target openshift version
+#[derive(Debug, Clone, Default)]
+pub struct OcpReport {
+    pub package: String,
+    pub target: String,
+    pub bundles: Vec<OcpBundle>,
+    // the first bundle on the path that allows the cluster upgrade (after the last blocking one)
+    pub minimum: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OcpBundle {
+    pub name: String,
+    pub max_ocp_version: Option<String>,
+    // set as a json number, trailing zeros are lost (4.10 reads as 4.1)
+    pub numeric: bool,
+    pub blocking: bool,
+}
+
+impl OcpReport {
+    // path is the ordered list of bundle names, bundles are all the bundles of the package
+    pub fn new(package: String, target: String, path: &[String], bundles: &[OlmBundle]) -> Self {
+        let mut report = OcpReport {
+            package,
+            target: target.clone(),
+            bundles: vec![],
+            minimum: None,
+        };
+        let target_version = parse_ocp_version(&target);
+        let mut last_blocking: Option<usize> = None;
+        for (i, name) in path.iter().enumerate() {
+            let bundle = bundles.iter().find(|b| &b.name == name);
+            let max = bundle.and_then(max_ocp_version);
+            let blocking = match (&max, target_version) {
+                (Some(m), Some(t)) => parse_ocp_version(m).is_some_and(|v| v < t),
+                _ => false,
+            };
+            if blocking {
+                last_blocking = Some(i);
+            }
+            report.bundles.push(OcpBundle {
+                name: name.clone(),
+                max_ocp_version: max,
+                numeric: bundle
+                    .and_then(max_ocp_value)
+                    .is_some_and(|v| v.is_number()),
+                blocking,
+            });
+        }
+        report.minimum = match last_blocking {
+            Some(i) => path.get(i + 1).cloned(),
+            None => path.first().cloned(),
+        };
+        report
+    }
+
+    pub fn blocking(&self) -> Vec<&OcpBundle> {
+        self.bundles.iter().filter(|b| b.blocking).collect()
+    }
+
+    pub fn display(&self, log: &Logging) {
+        log.hi(&format!("    openshift {} upgrade check", self.target));
+        for b in self.bundles.iter().filter(|b| b.numeric) {
+            log.warn(&format!(
+                "    {} olm.maxOpenShiftVersion is a number ({}), a version like 4.10 can't be told apart from 4.1",
+                b.name,
+                b.max_ocp_version.as_ref().unwrap()
+            ));
+        }
+        let blocking = self.blocking();
+        if blocking.len() == 0 {
+            log.hi("    no bundles on the path block the upgrade");
+            return;
+        }
+        for b in blocking.iter() {
+            log.warn(&format!(
+                "    {} blocks the upgrade (olm.maxOpenShiftVersion {})",
+                b.name,
+                b.max_ocp_version.as_ref().unwrap()
+            ));
+        }
+        match &self.minimum {
+            Some(m) => log.hi(&format!(
+                "    minimum bundle before upgrading to openshift {} : {}",
+                self.target, m
+            )),
+            None => log.warn(&format!(
+                "    no bundle on the path allows upgrading {} to openshift {}",
+                self.package, self.target
+            )),
+        }
+    }
+}
+
+// the olm.maxOpenShiftVersion of a bundle, set as a bundle property or (older catalogs)
+// in the olm.properties annotation of the csv
+pub fn max_ocp_version(bundle: &OlmBundle) -> Option<String> {
+    max_ocp_value(bundle).as_ref().and_then(value_to_version)
+}
+
+// the raw olm.maxOpenShiftVersion value (a string or a number)
+fn max_ocp_value(bundle: &OlmBundle) -> Option<Value> {
+    let values = bundle.get_properties("olm.maxOpenShiftVersion");
+    if let Some(v) = values.first() {
+        return Some(v.clone());
+    }
+    let metadata = bundle.csv_metadata()?;
+    let annotation = metadata["annotations"]["olm.properties"].as_str()?;
+    let props: Vec<Value> = serde_json::from_str(annotation).ok()?;
+    props
+        .iter()
+        .find(|p| p["type"] == "olm.maxOpenShiftVersion")
+        .map(|p| p["value"].clone())
+}
+
+// openshift versions are compared on major.minor (i.e 4.14, v4.14 or 4.14.3)
+pub fn parse_ocp_version(version: &str) -> Option<(u64, u64)> {
+    let mut parts = version.trim().trim_start_matches('v').split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().unwrap_or("0").parse().ok()?;
+    Some((major, minor))
+}
+
+// the property value is either a string or a number (i.e 4.8), numbers lose
+// trailing zeros when parsed (4.10 -> 4.1), see OcpBundle::numeric
+fn value_to_version(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // this brings everything from parent's scope into this scope
+    use super::*;
+
+    #[test]
+    fn ocp_report_pass() {
+        let bundles: Vec<OlmBundle> = serde_json::from_str(
+            "[
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.1\", \"package\": \"test-operator\",
+              \"properties\": [ { \"type\": \"olm.maxOpenShiftVersion\", \"value\": 4.12 } ] },
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.2\", \"package\": \"test-operator\",
+              \"properties\": [ { \"type\": \"olm.csv.metadata\", \"value\": {
+                \"annotations\": { \"olm.properties\": \"[{\\\"type\\\": \\\"olm.maxOpenShiftVersion\\\", \\\"value\\\": \\\"4.14\\\"}]\" } } } ] },
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.3\", \"package\": \"test-operator\" },
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.4\", \"package\": \"test-operator\",
+              \"properties\": [ { \"type\": \"olm.maxOpenShiftVersion\", \"value\": 4.10 } ] },
+            { \"schema\": \"olm.bundle\", \"name\": \"test-operator.v0.0.5\", \"package\": \"test-operator\",
+              \"properties\": [ { \"type\": \"olm.maxOpenShiftVersion\", \"value\": \"4.10\" } ] }
+            ]",
+        )
+        .expect("should parse bundles");
+        assert_eq!(max_ocp_version(&bundles[0]), Some("4.12".to_string()));
+        assert_eq!(max_ocp_version(&bundles[1]), Some("4.14".to_string()));
+        assert_eq!(parse_ocp_version("v4.16.2"), Some((4, 16)));
+        assert_eq!(parse_ocp_version("four"), None);
+
+        let path = vec![
+            "test-operator.v0.0.1".to_string(),
+            "test-operator.v0.0.2".to_string(),
+            "test-operator.v0.0.3".to_string(),
+        ];
+        let report = OcpReport::new(
+            "test-operator".to_string(),
+            "4.13".to_string(),
+            &path,
+            &bundles,
+        );
+        assert_eq!(report.blocking().len(), 1);
+        assert_eq!(report.minimum, Some("test-operator.v0.0.2".to_string()));
+
+        let report = OcpReport::new(
+            "test-operator".to_string(),
+            "4.16".to_string(),
+            &path,
+            &bundles,
+        );
+        assert_eq!(report.blocking().len(), 2);
+        assert_eq!(report.minimum, Some("test-operator.v0.0.3".to_string()));
+
+        let report = OcpReport::new(
+            "test-operator".to_string(),
+            "4.12".to_string(),
+            &path,
+            &bundles,
+        );
+        assert_eq!(report.blocking().len(), 0);
+        assert_eq!(report.minimum, Some("test-operator.v0.0.1".to_string()));
+
+        // a numeric 4.10 reads as 4.1 and is flagged, the string keeps 4.10
+        assert_eq!(max_ocp_version(&bundles[3]), Some("4.1".to_string()));
+        assert_eq!(max_ocp_version(&bundles[4]), Some("4.10".to_string()));
+        let path = vec![
+            "test-operator.v0.0.4".to_string(),
+            "test-operator.v0.0.5".to_string(),
+        ];
+        let report = OcpReport::new(
+            "test-operator".to_string(),
+            "4.10".to_string(),
+            &path,
+            &bundles,
+        );
+        assert_eq!(report.bundles[0].numeric, true);
+        assert_eq!(report.bundles[1].numeric, false);
+        assert_eq!(report.bundles[1].blocking, false);
+        report.display(&Logging {
+            log_level: Level::INFO,
+        });
+    }
+}